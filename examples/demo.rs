@@ -0,0 +1,304 @@
+//! The pathfinding demo: a generated world with a player to order around, wanderers to
+//! follow or chase, and every debugging aid switched on.
+//!
+//! Set `WORLD_SEED` to regenerate a layout and `WORLD_GEN_MODE` to scatter, maze, rooms
+//! or city to pick the kind of layout.
+
+use bevy::{
+    color::palettes::css::GOLD,
+    diagnostic::{DiagnosticPath, DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+    prelude::*,
+    window::PresentMode,
+};
+use my_bevy_game::command_queue::CommandQueue;
+use my_bevy_game::level_generators::generate_world;
+use my_bevy_game::obstacle_editor::EditorRng;
+use my_bevy_game::obstacles::*;
+use my_bevy_game::pathfinding_diagnostics::PathfindingDiagnosticsPlugin;
+use my_bevy_game::stuck_detection::PathProgress;
+use my_bevy_game::wanderer::{spawn_wanderers, wander, WanderRng};
+use my_bevy_game::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+#[derive(Component)]
+struct FpsText;
+
+#[derive(Component)]
+struct PathfindingStatsText;
+
+/// Environment variable that fixes the world generation seed.
+const WORLD_SEED_VAR: &str = "WORLD_SEED";
+/// Environment variable that picks the layout: scatter, maze, rooms or city.
+const WORLD_GEN_MODE_VAR: &str = "WORLD_GEN_MODE";
+
+/// A glTF scene placed as a `NavObstacle`, whose footprint comes from its meshes.
+const SCENE_OBSTACLE: &str = "models/AlienCake/cakeBirthday.glb";
+const SCENE_OBSTACLE_POSITION: Vec3 = Vec3::new(8.0, 0.0, 8.0);
+
+/// The pathfinding diagnostics shown on the HUD, in the order of its text sections.
+const PATHFINDING_HUD_ROWS: [(&str, DiagnosticPath); 5] = [
+    ("query time: ", PathfindingDiagnosticsPlugin::QUERY_TIME),
+    (
+        "nodes expanded: ",
+        PathfindingDiagnosticsPlugin::NODES_EXPANDED,
+    ),
+    ("LOS tests: ", PathfindingDiagnosticsPlugin::LOS_TESTS),
+    (
+        "queries/frame: ",
+        PathfindingDiagnosticsPlugin::QUERIES_PER_FRAME,
+    ),
+    (
+        "LOS cache hits: ",
+        PathfindingDiagnosticsPlugin::CACHE_HIT_RATE,
+    ),
+];
+
+fn main() {
+    let seed = std::env::var(WORLD_SEED_VAR)
+        .ok()
+        .and_then(|seed| seed.parse().ok())
+        .unwrap_or_else(rand::random);
+    let mut world_gen_config = WorldGenConfig::with_seed(seed);
+    if let Some(mode) = std::env::var(WORLD_GEN_MODE_VAR)
+        .ok()
+        .and_then(|mode| WorldGenMode::from_name(&mode))
+    {
+        world_gen_config.mode = mode;
+    }
+    world_gen_config.keep_clear_zones.push(KeepClearZone {
+        center: SCENE_OBSTACLE_POSITION,
+        radius: 4.0,
+    });
+    let pathfinding_config = PathfindingConfig::default();
+    world_gen_config.agent_buffer = pathfinding_config.agent_buffer;
+    world_gen_config.corner_join = pathfinding_config.corner_join;
+
+    App::new()
+        .add_plugins((
+            DefaultPlugins
+                .set(WindowPlugin {
+                    primary_window: Some(Window {
+                        present_mode: PresentMode::Immediate,
+                        ..default()
+                    }),
+                    ..default()
+                })
+                // Edits to the level file show up in the running game
+                .set(AssetPlugin {
+                    watch_for_changes_override: Some(true),
+                    ..default()
+                }),
+            FrameTimeDiagnosticsPlugin,
+            PathfindingPlugin {
+                config: pathfinding_config,
+            },
+            CameraControlPlugin,
+            DebugGizmosPlugin,
+        ))
+        .insert_resource(world_gen_config)
+        .add_systems(Startup, setup)
+        .add_systems(Update, wander.in_set(PathfindingSet::Planning))
+        .add_systems(Update, (text_update_system, pathfinding_text_update_system))
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    asset_server: Res<AssetServer>,
+    world_gen_config: Res<WorldGenConfig>,
+) {
+    let mut obstacle_polygons = ObstaclePolygons::new();
+    let transforms_and_scales = generate_world(&world_gen_config, &mut obstacle_polygons)
+        .unwrap_or_else(|error| {
+            println!("Invalid world generation settings: {error}");
+            Vec::new()
+        });
+    let mut rng = world_gen_config.detail_rng();
+    render_cuboids(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        transforms_and_scales,
+        &mut rng,
+    );
+
+    spawn_wanderers(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        &obstacle_polygons,
+        &mut rng,
+    );
+    commands.insert_resource(EditorRng(StdRng::seed_from_u64(rng.gen())));
+    commands.insert_resource(WanderRng(rng));
+
+    commands.spawn((
+        SceneBundle {
+            scene: asset_server.load(GltfAssetLabel::Scene(0).from_asset(SCENE_OBSTACLE)),
+            transform: Transform::from_translation(SCENE_OBSTACLE_POSITION)
+                .with_rotation(Quat::from_rotation_y(0.6))
+                .with_scale(Vec3::splat(8.0)),
+            ..default()
+        },
+        NavObstacle,
+    ));
+
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(Cuboid::new(1.0, 1.0, 1.0)),
+            material: materials.add(Color::WHITE),
+            transform: Transform::from_xyz(0.0, 0.5, 0.0),
+            ..default()
+        },
+        Player,
+        PlayerStats::new(5.0, 100.0, 1.0),
+        TargetPosition::default(),
+        GizmoPath::default(),
+        LastTargetPosition::default(),
+        CommandQueue::default(),
+        PathProgress::default(),
+    ));
+
+    commands.spawn(Camera3dBundle {
+        transform: Transform::from_xyz(16.875, 16.875, 0.0).looking_at(Vec3::ZERO, Vec3::Y),
+        ..default()
+    });
+
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(Plane3d::default().mesh().size(GROUND_SIZE, GROUND_SIZE)),
+            material: materials.add(Color::srgb(0.3, 0.5, 0.3)),
+            ..default()
+        },
+        Ground,
+    ));
+
+    commands.spawn(DirectionalLightBundle {
+        directional_light: DirectionalLight {
+            shadows_enabled: true,
+            ..default()
+        },
+        transform: Transform::from_translation(Vec3::ONE).looking_at(Vec3::ZERO, Vec3::Y),
+        ..default()
+    });
+
+    commands.spawn((
+        TextBundle::from_sections([
+            TextSection::new(
+                "FPS: ",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 60.0,
+                    ..default()
+                },
+            ),
+            TextSection::new(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                    font_size: 60.0,
+                    color: GOLD.into(),
+                },
+            ),
+        ]),
+        FpsText,
+    ));
+
+    let mut stats_sections = Vec::new();
+    for (label, _) in PATHFINDING_HUD_ROWS {
+        stats_sections.push(TextSection::new(
+            label,
+            TextStyle {
+                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                font_size: 20.0,
+                ..default()
+            },
+        ));
+        stats_sections.push(TextSection::new(
+            "-\n",
+            TextStyle {
+                font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                font_size: 20.0,
+                color: GOLD.into(),
+            },
+        ));
+    }
+
+    commands.spawn((
+        TextBundle::from_sections(stats_sections).with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(70.0),
+            left: Val::Px(0.0),
+            ..default()
+        }),
+        PathfindingStatsText,
+    ));
+
+    // Run with WORLD_SEED set to this to get the same layout again
+    commands.spawn(
+        TextBundle::from_sections([
+            TextSection::new(
+                "seed: ",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 20.0,
+                    ..default()
+                },
+            ),
+            TextSection::new(
+                world_gen_config.seed.to_string(),
+                TextStyle {
+                    font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                    font_size: 20.0,
+                    color: GOLD.into(),
+                },
+            ),
+        ])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(10.0),
+            left: Val::Px(10.0),
+            ..default()
+        }),
+    );
+}
+
+fn text_update_system(
+    diagnostics: Res<DiagnosticsStore>,
+    mut query: Query<&mut Text, With<FpsText>>,
+) {
+    for mut text in &mut query {
+        if let Some(fps) = diagnostics.get(&FrameTimeDiagnosticsPlugin::FPS) {
+            if let Some(value) = fps.smoothed() {
+                text.sections[1].value = format!("{value:.2}");
+            }
+        }
+    }
+}
+
+fn pathfinding_text_update_system(
+    diagnostics: Res<DiagnosticsStore>,
+    mut query: Query<&mut Text, With<PathfindingStatsText>>,
+) {
+    for mut text in &mut query {
+        for (row, (_, path)) in PATHFINDING_HUD_ROWS.iter().enumerate() {
+            let Some(diagnostic) = diagnostics.get(path) else {
+                continue;
+            };
+            let Some(smoothed) = diagnostic.smoothed() else {
+                continue;
+            };
+
+            // Smoothed value, then the average and peak over the kept history
+            let average = diagnostic.average().unwrap_or(smoothed);
+            let peak = diagnostic.values().copied().fold(f64::MIN, f64::max);
+            text.sections[row * 2 + 1].value = format!(
+                "{smoothed:.2}{suffix} (avg {average:.2}, max {peak:.2})\n",
+                suffix = diagnostic.suffix,
+            );
+        }
+    }
+}
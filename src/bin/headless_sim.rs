@@ -0,0 +1,61 @@
+//! Runs a scripted scenario without a window or GPU and writes a JSON report of arrival
+//! times, path lengths, collisions and stuck events.
+//!
+//! Usage: `headless_sim <scenario.ron> [report.json]`. Without a report path the report
+//! is written next to the scenario, as `<scenario>.report.json`.
+//!
+//! A scenario names a level file relative to itself, the agents and their timed orders:
+//!
+//! ```ron
+//! (
+//!     level: Some("level.nav.ron"),
+//!     timestep: 0.02,
+//!     agents: [(name: "a", position: (0.0, 0.4, 0.0), speed: 4.0)],
+//!     commands: [(time: 0.0, agent: "a", target: (20.0, 0.4, 5.0))],
+//! )
+//! ```
+
+use my_bevy_game::simulation::{run_scenario, SimScenario};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (scenario_path, report_path) = match args.as_slice() {
+        [scenario] => (
+            PathBuf::from(scenario),
+            PathBuf::from(scenario).with_extension("report.json"),
+        ),
+        [scenario, report] => (PathBuf::from(scenario), PathBuf::from(report)),
+        _ => {
+            eprintln!("usage: headless_sim <scenario.ron> [report.json]");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let report =
+        match SimScenario::load(&scenario_path).and_then(|scenario| run_scenario(&scenario)) {
+            Ok(report) => report,
+            Err(error) => {
+                eprintln!("Failed to run {}: {error}", scenario_path.display());
+                return ExitCode::FAILURE;
+            }
+        };
+
+    let json = serde_json::to_string_pretty(&report).expect("the report serializes to JSON");
+    if let Err(error) = std::fs::write(&report_path, json) {
+        eprintln!("Could not write {}: {error}", report_path.display());
+        return ExitCode::FAILURE;
+    }
+    println!(
+        "Simulated {:.2}s{}, wrote {}.",
+        report.simulated_time,
+        if report.completed {
+            ""
+        } else {
+            " (time limit reached)"
+        },
+        report_path.display()
+    );
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,68 @@
+//! Runs a Moving AI Lab benchmark headless: every scenario of a `.scen` file through
+//! each planner on its `.map`, comparing the path lengths with the reference ones.
+//!
+//! Usage: `movingai <file.map> <file.scen> [thetastar|astar|exact ...]`. Without
+//! planners Theta* and A* are run.
+
+use my_bevy_game::movingai::{load_scenarios, run_scenarios, GridMap};
+use my_bevy_game::pathfinding::Planner;
+use std::process::ExitCode;
+
+fn parse_planner(name: &str) -> Option<Planner> {
+    match name.to_lowercase().as_str() {
+        "thetastar" | "theta" => Some(Planner::ThetaStar),
+        "astar" => Some(Planner::AStar),
+        "exact" => Some(Planner::Exact),
+        _ => None,
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [map_path, scen_path, planner_names @ ..] = args.as_slice() else {
+        eprintln!("usage: movingai <file.map> <file.scen> [thetastar|astar|exact ...]");
+        return ExitCode::FAILURE;
+    };
+
+    let mut planners = Vec::new();
+    for name in planner_names {
+        match parse_planner(name) {
+            Some(planner) => planners.push(planner),
+            None => {
+                eprintln!("unknown planner `{name}`");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    if planners.is_empty() {
+        planners = vec![Planner::ThetaStar, Planner::AStar];
+    }
+
+    let map = match GridMap::load(map_path) {
+        Ok(map) => map,
+        Err(error) => {
+            eprintln!("Failed to load {map_path}: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let scenarios = match load_scenarios(scen_path) {
+        Ok(scenarios) => scenarios,
+        Err(error) => {
+            eprintln!("Failed to load {scen_path}: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    println!(
+        "{map_path}: {}x{}, {} obstacle rectangles, {} scenarios",
+        map.width,
+        map.height,
+        // Less the map outline
+        map.obstacle_polygons().polygons.len() - 1,
+        scenarios.len()
+    );
+    for planner in planners {
+        println!("{}", run_scenarios(&map, &scenarios, planner));
+    }
+    ExitCode::SUCCESS
+}
@@ -23,8 +23,11 @@ pub fn camera_follow(
     camera_follow_toggle: Res<CameraFollowToggle>,
 ) {
     if camera_follow_toggle.0 {
-        let player_transform = player_query.single();
-        let mut camera_transform = camera_query.single_mut();
+        let (Ok(player_transform), Ok(mut camera_transform)) =
+            (player_query.get_single(), camera_query.get_single_mut())
+        else {
+            return;
+        };
 
         // Set the camera position to be at a fixed offset from the player
         let offset = Vec3::new(16.875, 16.875, 0.0);
@@ -42,7 +45,9 @@ pub fn camera_zoom(
         camera_zoom.0 -= ev.y * zoom_speed;
         camera_zoom.0 = camera_zoom.0.clamp(5.0, 40.0);
 
-        let mut camera_transform = camera_query.single_mut();
+        let Ok(mut camera_transform) = camera_query.get_single_mut() else {
+            return;
+        };
         let forward = camera_transform.forward();
         camera_transform.translation += forward * ev.y * zoom_speed;
     }
@@ -58,8 +63,11 @@ pub fn camera_edge_pan(
         return; // Don't pan if camera is following the player
     }
 
-    let window = windows.single();
-    let mut camera_transform = camera_query.single_mut();
+    let (Ok(window), Ok(mut camera_transform)) =
+        (windows.get_single(), camera_query.get_single_mut())
+    else {
+        return;
+    };
 
     if let Some(cursor_position) = window.cursor_position() {
         let window_size = Vec2::new(window.width(), window.height());
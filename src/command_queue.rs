@@ -0,0 +1,162 @@
+use crate::cursor::CursorPosition;
+use crate::obstacles::ObstaclePolygons;
+use crate::pathfinding::{find_path, NavMesh, PathQueryStats};
+use crate::player::{GizmoPath, Player, TargetPosition};
+use crate::pursue::Pursue;
+use bevy::prelude::*;
+use std::collections::VecDeque;
+
+/// A destination waiting in an agent's command queue, together with the path that
+/// leads to it from `start`, the end of the previous leg. A direct path holds only the
+/// destination.
+#[derive(Debug, Clone)]
+pub struct QueuedLeg {
+    pub start: Vec3,
+    pub destination: Vec3,
+    pub path: Vec<Vec3>,
+}
+
+#[derive(Component, Default)]
+pub struct CommandQueue {
+    pub legs: VecDeque<QueuedLeg>,
+    pub patrol: bool,
+}
+
+impl CommandQueue {
+    pub fn clear(&mut self) {
+        self.legs.clear();
+        self.patrol = false;
+    }
+
+    /// The point a newly appended leg starts from: the end of the last queued leg,
+    /// otherwise the end of the path being followed, otherwise the agent's position.
+    pub fn next_leg_start(&self, target_position: &TargetPosition, position: Vec3) -> Vec3 {
+        if let Some(leg) = self.legs.back() {
+            return leg.destination;
+        }
+        match &target_position.0 {
+            Some(path) if !path.is_empty() => path[path.len() - 1],
+            _ => position,
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn handle_shift_right_click_queue_target(
+    mut commands: Commands,
+    buttons: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    cursor_position: Res<CursorPosition>,
+    mut player_query: Query<(Entity, &Transform, &TargetPosition, &mut CommandQueue), With<Player>>,
+    obstacle_polygons: Res<ObstaclePolygons>,
+    nav_mesh: Res<NavMesh>,
+    mut query_stats: Deferred<PathQueryStats>,
+) {
+    if !buttons.just_pressed(MouseButton::Right)
+        || !keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight])
+    {
+        return;
+    }
+
+    let Some(goal_position) = cursor_position.0 else {
+        return;
+    };
+
+    let Ok((player, player_transform, target_position, mut command_queue)) =
+        player_query.get_single_mut()
+    else {
+        return;
+    };
+    let start_position =
+        command_queue.next_leg_start(target_position, player_transform.translation);
+
+    let Some(path) = find_path(
+        &nav_mesh,
+        &obstacle_polygons,
+        start_position,
+        goal_position,
+        &mut query_stats,
+    ) else {
+        println!("No valid path found.");
+        return;
+    };
+
+    command_queue.legs.push_back(QueuedLeg {
+        start: start_position,
+        destination: goal_position,
+        path,
+    });
+    commands.entity(player).remove::<Pursue>();
+}
+
+pub fn toggle_patrol(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut player_query: Query<(&Transform, &TargetPosition, &mut CommandQueue), With<Player>>,
+    obstacle_polygons: Res<ObstaclePolygons>,
+    nav_mesh: Res<NavMesh>,
+    mut query_stats: Deferred<PathQueryStats>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::KeyP) {
+        return;
+    }
+
+    let Ok((player_transform, target_position, mut command_queue)) = player_query.get_single_mut()
+    else {
+        return;
+    };
+
+    if command_queue.patrol {
+        // The remaining legs are still walked once, they just stop being recycled
+        command_queue.patrol = false;
+        return;
+    }
+
+    let Some(last_destination) = command_queue.legs.back().map(|leg| leg.destination) else {
+        return;
+    };
+
+    // Close the loop with a leg from the last queued destination to the route's start
+    let loop_start = match &target_position.0 {
+        Some(path) if !path.is_empty() => path[path.len() - 1],
+        _ => player_transform.translation,
+    };
+
+    let Some(path) = find_path(
+        &nav_mesh,
+        &obstacle_polygons,
+        last_destination,
+        loop_start,
+        &mut query_stats,
+    ) else {
+        println!("No valid path found.");
+        return;
+    };
+
+    command_queue.legs.push_back(QueuedLeg {
+        start: last_destination,
+        destination: loop_start,
+        path,
+    });
+    command_queue.patrol = true;
+}
+
+pub fn advance_command_queue(
+    mut agent_query: Query<(&mut TargetPosition, &mut GizmoPath, &mut CommandQueue)>,
+) {
+    for (mut target_position, mut gizmo_path, mut command_queue) in &mut agent_query {
+        if matches!(&target_position.0, Some(path) if !path.is_empty()) {
+            continue;
+        }
+
+        let Some(leg) = command_queue.legs.pop_front() else {
+            continue;
+        };
+
+        target_position.0 = Some(leg.path.clone());
+        gizmo_path.0 = Some(leg.path.clone());
+
+        if command_queue.patrol {
+            command_queue.legs.push_back(leg);
+        }
+    }
+}
@@ -11,10 +11,14 @@ pub fn draw_cursor(
     mut cursor_position_res: ResMut<CursorPosition>,
     mut gizmos: Gizmos,
 ) {
-    let (camera, camera_transform) = camera_query.single();
-    let ground = ground_query.single();
+    let Ok((camera, camera_transform)) = camera_query.get_single() else {
+        return;
+    };
+    let Ok(ground) = ground_query.get_single() else {
+        return;
+    };
 
-    let Some(cursor_position) = windows.single().cursor_position() else {
+    let Some(cursor_position) = windows.get_single().ok().and_then(Window::cursor_position) else {
         return;
     };
 
@@ -0,0 +1,304 @@
+use crate::obstacles::{
+    spawn_cuboid, spawn_polygon_obstacle, spawn_primitive_obstacle, CuboidObstacle,
+    PolygonObstacle, PrimitiveObstacle, WalkableArea,
+};
+use crate::pathfinding::NavMesh;
+use crate::player::Player;
+use crate::utils::{Point, Polygon};
+use crate::wanderer::Wanderer;
+use crate::{Ground, GROUND_SIZE};
+use bevy::prelude::*;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+/// Where F9 saves the current level, and where F10 loads it from through the asset
+/// server.
+pub const LEVEL_PATH: &str = "assets/levels/level.nav.ron";
+pub const LEVEL_ASSET_PATH: &str = "levels/level.nav.ron";
+
+/// A cuboid as `render_cuboids` spawns it: the transform, the mesh size and the colour.
+#[derive(Serialize, Deserialize)]
+pub struct CuboidData {
+    pub translation: [f32; 3],
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+    pub size: [f32; 3],
+    pub color: [f32; 3],
+}
+
+/// A free-form obstacle: its footprint on the ground plane (x, z), before inflation,
+/// and the height it is extruded to.
+#[derive(Serialize, Deserialize)]
+pub struct PolygonData {
+    pub footprint: Vec<[f32; 2]>,
+    pub height: f32,
+    pub color: [f32; 3],
+}
+
+/// A circle, capsule or wall, placed by its translation and rotation.
+#[derive(Serialize, Deserialize)]
+pub struct PrimitiveData {
+    pub translation: [f32; 3],
+    pub rotation: [f32; 4],
+    pub shape: PrimitiveObstacle,
+    pub color: [f32; 3],
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SpawnPoints {
+    pub player: [f32; 3],
+    pub wanderers: Vec<[f32; 3]>,
+}
+
+/// Everything needed to rebuild a world. The nav mesh is derived from the obstacles
+/// unless a baked one is stored along with them.
+#[derive(Serialize, Deserialize)]
+pub struct LevelFile {
+    pub ground_size: f32,
+    pub spawn_points: SpawnPoints,
+    pub cuboids: Vec<CuboidData>,
+    #[serde(default)]
+    pub polygons: Vec<PolygonData>,
+    #[serde(default)]
+    pub primitives: Vec<PrimitiveData>,
+    /// Outline (x, z) of the walkable area, when the level is not open all around.
+    #[serde(default)]
+    pub walkable_area: Option<Vec<[f32; 2]>>,
+    #[serde(default)]
+    pub nav_mesh: Option<Vec<[f32; 3]>>,
+}
+
+#[derive(Debug)]
+pub enum LevelError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LevelError::Io(error) => write!(f, "{error}"),
+            LevelError::Parse(error) => write!(f, "{error}"),
+            LevelError::Serialize(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+impl LevelFile {
+    pub fn from_ron(bytes: &[u8]) -> Result<Self, LevelError> {
+        ron::de::from_bytes(bytes).map_err(LevelError::Parse)
+    }
+
+    pub fn to_ron(&self) -> Result<String, LevelError> {
+        ron::ser::to_string_pretty(self, PrettyConfig::default()).map_err(LevelError::Serialize)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), LevelError> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(LevelError::Io)?;
+        }
+        std::fs::write(path, self.to_ron()?).map_err(LevelError::Io)
+    }
+
+    /// The ground plane scale that gives it the level's extent.
+    pub fn ground_scale(&self) -> Vec3 {
+        let scale = self.ground_size / GROUND_SIZE;
+        Vec3::new(scale, 1.0, scale)
+    }
+
+    pub fn baked_nav_mesh(&self) -> Option<NavMesh> {
+        self.nav_mesh.as_ref().map(|vertices| {
+            let mut nav_mesh = NavMesh::new();
+            for vertex in vertices {
+                nav_mesh.add_vertex(Point::from(Vec3::from_array(*vertex)));
+            }
+            nav_mesh
+        })
+    }
+}
+
+/// A baked nav mesh from a loaded level, applied once the obstacles it came with have
+/// been rebuilt so that it replaces the derived one.
+#[derive(Resource, Default)]
+pub struct BakedNavMesh(pub Option<NavMesh>);
+
+fn color_to_array(color: Color) -> [f32; 3] {
+    let color = color.to_srgba();
+    [color.red, color.green, color.blue]
+}
+
+fn material_color(
+    materials: &Assets<StandardMaterial>,
+    material: &Handle<StandardMaterial>,
+) -> [f32; 3] {
+    materials.get(material).map_or([1.0, 1.0, 1.0], |material| {
+        color_to_array(material.base_color)
+    })
+}
+
+/// F9 saves the level, Shift+F9 saves it along with the current nav mesh.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn save_level(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    materials: Res<Assets<StandardMaterial>>,
+    cuboid_query: Query<(&Transform, &CuboidObstacle, &Handle<StandardMaterial>)>,
+    polygon_query: Query<(&PolygonObstacle, &Handle<StandardMaterial>)>,
+    primitive_query: Query<(&Transform, &PrimitiveObstacle, &Handle<StandardMaterial>)>,
+    area_query: Query<&WalkableArea>,
+    player_query: Query<&Transform, With<Player>>,
+    wanderer_query: Query<&Transform, With<Wanderer>>,
+    ground_query: Query<&Transform, With<Ground>>,
+    nav_mesh: Res<NavMesh>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F9) {
+        return;
+    }
+    let bake_nav_mesh =
+        keyboard_input.pressed(KeyCode::ShiftLeft) || keyboard_input.pressed(KeyCode::ShiftRight);
+    let (Ok(ground_transform), Ok(player_transform)) =
+        (ground_query.get_single(), player_query.get_single())
+    else {
+        println!("A level needs one ground and one player to be saved.");
+        return;
+    };
+
+    let level = LevelFile {
+        ground_size: GROUND_SIZE * ground_transform.scale.x,
+        spawn_points: SpawnPoints {
+            player: player_transform.translation.to_array(),
+            wanderers: wanderer_query
+                .iter()
+                .map(|transform| transform.translation.to_array())
+                .collect(),
+        },
+        cuboids: cuboid_query
+            .iter()
+            .map(|(transform, cuboid, material)| CuboidData {
+                translation: transform.translation.to_array(),
+                rotation: transform.rotation.to_array(),
+                scale: transform.scale.to_array(),
+                size: cuboid.size.to_array(),
+                color: material_color(&materials, material),
+            })
+            .collect(),
+        polygons: polygon_query
+            .iter()
+            .map(|(obstacle, material)| PolygonData {
+                footprint: obstacle
+                    .footprint
+                    .vertices
+                    .iter()
+                    .map(|vertex| [vertex.x, vertex.z])
+                    .collect(),
+                height: obstacle.height,
+                color: material_color(&materials, material),
+            })
+            .collect(),
+        primitives: primitive_query
+            .iter()
+            .map(|(transform, primitive, material)| PrimitiveData {
+                translation: transform.translation.to_array(),
+                rotation: transform.rotation.to_array(),
+                shape: *primitive,
+                color: material_color(&materials, material),
+            })
+            .collect(),
+        walkable_area: area_query.get_single().ok().map(|area| {
+            area.boundary
+                .vertices
+                .iter()
+                .map(|vertex| [vertex.x, vertex.z])
+                .collect()
+        }),
+        nav_mesh: bake_nav_mesh.then(|| {
+            nav_mesh
+                .vertices
+                .iter()
+                .map(|vertex| Vec3::from(vertex).to_array())
+                .collect()
+        }),
+    };
+
+    match level.save(LEVEL_PATH) {
+        Ok(()) => println!("Saved the level to {LEVEL_PATH}."),
+        Err(error) => println!("Could not save the level to {LEVEL_PATH}: {error}"),
+    }
+}
+
+/// Spawns the obstacles and walkable area of a level. The obstacle polygons and nav
+/// mesh follow from the spawned obstacles.
+pub fn spawn_level_obstacles(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    level: &LevelFile,
+) {
+    for cuboid in &level.cuboids {
+        let transform = Transform {
+            translation: Vec3::from_array(cuboid.translation),
+            rotation: Quat::from_array(cuboid.rotation),
+            scale: Vec3::from_array(cuboid.scale),
+        };
+        let [red, green, blue] = cuboid.color;
+        spawn_cuboid(
+            commands,
+            meshes,
+            materials,
+            transform,
+            Vec3::from_array(cuboid.size),
+            Color::srgb(red, green, blue),
+        );
+    }
+
+    for polygon in &level.polygons {
+        let mut footprint = Polygon::new();
+        for [x, z] in &polygon.footprint {
+            footprint.add_vertex(*x, 0.0, *z);
+        }
+        let [red, green, blue] = polygon.color;
+        spawn_polygon_obstacle(
+            commands,
+            meshes,
+            materials,
+            footprint,
+            polygon.height,
+            Color::srgb(red, green, blue),
+        );
+    }
+
+    for primitive in &level.primitives {
+        let [red, green, blue] = primitive.color;
+        spawn_primitive_obstacle(
+            commands,
+            meshes,
+            materials,
+            Transform::from_translation(Vec3::from_array(primitive.translation))
+                .with_rotation(Quat::from_array(primitive.rotation)),
+            primitive.shape,
+            Color::srgb(red, green, blue),
+        );
+    }
+
+    if let Some(outline) = &level.walkable_area {
+        let mut boundary = Polygon::new();
+        for [x, z] in outline {
+            boundary.add_vertex(*x, 0.0, *z);
+        }
+        commands.spawn(WalkableArea { boundary });
+    }
+}
+
+pub fn apply_baked_nav_mesh(
+    mut baked_nav_mesh: ResMut<BakedNavMesh>,
+    mut nav_mesh: ResMut<NavMesh>,
+) {
+    if let Some(baked) = baked_nav_mesh.0.take() {
+        *nav_mesh = baked.with_planner(nav_mesh.planner);
+    }
+}
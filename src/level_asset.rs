@@ -0,0 +1,169 @@
+use crate::command_queue::CommandQueue;
+use crate::level::{spawn_level_obstacles, BakedNavMesh, LevelError, LevelFile, LEVEL_ASSET_PATH};
+use crate::obstacles::{CuboidObstacle, PolygonObstacle, PrimitiveObstacle, WalkableArea};
+use crate::path_validation::RepathAllAgents;
+use crate::player::{GizmoPath, LastTargetPosition, Player, TargetPosition};
+use crate::pursue::Pursue;
+use crate::wanderer::{spawn_wanderer, Wanderer};
+use crate::Ground;
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use bevy::prelude::*;
+
+/// A level file loaded through the asset server.
+#[derive(Asset, TypePath)]
+pub struct NavLevel(pub LevelFile);
+
+#[derive(Default)]
+pub struct NavLevelLoader;
+
+impl AssetLoader for NavLevelLoader {
+    type Asset = NavLevel;
+    type Settings = ();
+    type Error = LevelError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext<'_>,
+    ) -> Result<NavLevel, LevelError> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(LevelError::Io)?;
+        LevelFile::from_ron(&bytes).map(NavLevel)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["nav.ron"]
+    }
+}
+
+/// The level loaded with F10, if any. Edits to its file are applied as they are saved.
+#[derive(Resource, Default)]
+pub struct CurrentLevel {
+    handle: Option<Handle<NavLevel>>,
+    respawn_agents: bool,
+}
+
+/// Registers the `.nav.ron` level asset. F10 loads `assets/levels/level.nav.ron`. With
+/// the `file_watcher` feature, on by default, and an `AssetPlugin` that watches for
+/// changes, saving that file rebuilds the obstacles in the running game and repaths
+/// every agent.
+#[derive(Default)]
+pub struct NavLevelPlugin;
+
+impl Plugin for NavLevelPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<NavLevel>()
+            .init_asset_loader::<NavLevelLoader>()
+            .init_resource::<CurrentLevel>();
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn load_level(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    asset_server: Res<AssetServer>,
+    levels: Res<Assets<NavLevel>>,
+    mut level_events: EventReader<AssetEvent<NavLevel>>,
+    mut current_level: ResMut<CurrentLevel>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    obstacle_query: Query<
+        Entity,
+        Or<(
+            With<CuboidObstacle>,
+            With<PolygonObstacle>,
+            With<PrimitiveObstacle>,
+            With<WalkableArea>,
+        )>,
+    >,
+    wanderer_query: Query<Entity, With<Wanderer>>,
+    player_query: Query<Entity, With<Player>>,
+    mut ground_query: Query<&mut Transform, With<Ground>>,
+    mut baked_nav_mesh: ResMut<BakedNavMesh>,
+    mut repath_all_agents: EventWriter<RepathAllAgents>,
+) {
+    let mut apply = false;
+
+    if keyboard_input.just_pressed(KeyCode::F10) {
+        let handle = asset_server.load(LEVEL_ASSET_PATH);
+        // A level that is already loaded sends no further event
+        apply = levels.contains(&handle);
+        current_level.handle = Some(handle);
+        current_level.respawn_agents = true;
+    }
+
+    let Some(handle) = current_level.handle.clone() else {
+        level_events.clear();
+        return;
+    };
+    for event in level_events.read() {
+        match event {
+            AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id }
+                if *id == handle.id() =>
+            {
+                apply = true;
+            }
+            _ => {}
+        }
+    }
+    if !apply {
+        return;
+    }
+    let Some(NavLevel(level)) = levels.get(&handle) else {
+        return;
+    };
+
+    for entity in &obstacle_query {
+        commands.entity(entity).despawn();
+    }
+    spawn_level_obstacles(&mut commands, &mut meshes, &mut materials, level);
+    if let Ok(mut ground_transform) = ground_query.get_single_mut() {
+        ground_transform.scale = level.ground_scale();
+    }
+    baked_nav_mesh.0 = level.baked_nav_mesh();
+
+    if std::mem::take(&mut current_level.respawn_agents) {
+        for entity in &wanderer_query {
+            commands.entity(entity).despawn();
+        }
+        for position in &level.spawn_points.wanderers {
+            spawn_wanderer(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                Vec3::from_array(*position),
+            );
+        }
+
+        // The player starts over at its spawn point without any orders
+        if let Ok(player) = player_query.get_single() {
+            commands
+                .entity(player)
+                .insert((
+                    Transform::from_translation(Vec3::from_array(level.spawn_points.player)),
+                    TargetPosition::default(),
+                    GizmoPath::default(),
+                    LastTargetPosition::default(),
+                    CommandQueue::default(),
+                ))
+                .remove::<Pursue>();
+        }
+
+        println!(
+            "Loaded {} cuboids, {} polygons and {} round or thin obstacles from {LEVEL_ASSET_PATH}.",
+            level.cuboids.len(),
+            level.polygons.len(),
+            level.primitives.len()
+        );
+    } else {
+        // Agents keep going, but along routes that fit the new obstacles
+        repath_all_agents.send(RepathAllAgents);
+        println!("Reloaded {LEVEL_ASSET_PATH}.");
+    }
+}
@@ -0,0 +1,379 @@
+use crate::obstacles::{
+    generate_cuboid_polygon, generate_cuboids, ObstaclePolygons, WorldGenConfig, WorldGenError,
+    WorldGenMode,
+};
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::Rng;
+
+/// Thickness of maze and room walls.
+const WALL_THICKNESS: f32 = 0.5;
+/// Height of maze and room walls.
+const WALL_HEIGHT: f32 = 1.0;
+/// Width of a maze cell, wall centre to wall centre.
+const MAZE_CELL_SIZE: f32 = 6.0;
+/// Rooms are not split any further once either side would drop below this.
+const MIN_ROOM_SIZE: f32 = 12.0;
+/// Width of the gap left in a wall as a doorway.
+const DOOR_WIDTH: f32 = 3.0;
+/// How many split positions are tried before a room is left whole.
+const SPLIT_ATTEMPTS: usize = 10;
+/// Side of a city block, without the streets around it.
+const BLOCK_SIZE: f32 = 20.0;
+const STREET_WIDTH: f32 = 6.0;
+const ALLEY_WIDTH: f32 = 2.5;
+/// The chance that a building lot is left empty as a square.
+const EMPTY_LOT_CHANCE: f64 = 0.15;
+
+/// Generates the obstacles of the configured layout. Every mode returns cuboids in the
+/// form `render_cuboids` takes and adds their footprints to `obstacle_polygons`.
+pub fn generate_world(
+    config: &WorldGenConfig,
+    obstacle_polygons: &mut ObstaclePolygons,
+) -> Result<Vec<(Transform, Vec3)>, WorldGenError> {
+    config.validate()?;
+    let boxes = match config.mode {
+        WorldGenMode::Scatter => return Ok(generate_cuboids(config, obstacle_polygons)),
+        WorldGenMode::Maze => generate_maze(config),
+        WorldGenMode::Rooms => generate_rooms(config),
+        WorldGenMode::City => generate_city(config),
+    };
+
+    // Layouts built from boxes keep the transform scale at one and size the mesh
+    let mut transforms_and_scales = Vec::new();
+    for (center, size) in boxes {
+        let transform = Transform::from_xyz(center.x, size.y / 2.0, center.y);
+        obstacle_polygons.add_polygon(generate_cuboid_polygon(
+            transform,
+            size.x,
+            size.y,
+            size.z,
+            config.agent_buffer,
+            config.corner_join,
+        ));
+        transforms_and_scales.push((transform, size));
+    }
+    Ok(transforms_and_scales)
+}
+
+/// An axis-aligned wall between two points on the ground plane (x, z).
+struct Wall {
+    start: Vec2,
+    end: Vec2,
+}
+
+impl Wall {
+    /// The wall as a box, lengthened by its thickness so that corners close up.
+    fn to_box(&self) -> (Vec2, Vec3) {
+        let extent = (self.end - self.start).abs() + Vec2::splat(WALL_THICKNESS);
+        (
+            (self.start + self.end) / 2.0,
+            Vec3::new(extent.x, WALL_HEIGHT, extent.y),
+        )
+    }
+}
+
+/// Whether a box keeps its inflated footprint out of every keep-clear zone.
+fn clear_of_zones(center: Vec2, size: Vec3, config: &WorldGenConfig) -> bool {
+    let half = Vec2::new(size.x, size.z) / 2.0 + Vec2::splat(config.agent_buffer);
+    config.keep_clear_zones.iter().all(|zone| {
+        let offset = (zone.center.xz() - center).abs() - half;
+        offset.max(Vec2::ZERO).length() >= zone.radius
+    })
+}
+
+/// Joins touching collinear walls so that long runs become a single obstacle.
+fn merge_walls(mut walls: Vec<Wall>) -> Vec<Wall> {
+    walls.sort_by(|a, b| {
+        let key = |wall: &Wall| {
+            let horizontal = wall.start.y == wall.end.y;
+            let (line, along) = if horizontal {
+                (wall.start.y, wall.start.x)
+            } else {
+                (wall.start.x, wall.start.y)
+            };
+            (!horizontal, line, along)
+        };
+        let (a, b) = (key(a), key(b));
+        a.0.cmp(&b.0)
+            .then(a.1.total_cmp(&b.1))
+            .then(a.2.total_cmp(&b.2))
+    });
+
+    let mut merged: Vec<Wall> = Vec::new();
+    for wall in walls {
+        if let Some(last) = merged.last_mut() {
+            let both_horizontal = last.start.y == last.end.y
+                && wall.start.y == wall.end.y
+                && last.start.y == wall.start.y;
+            let both_vertical = last.start.x == last.end.x
+                && wall.start.x == wall.end.x
+                && last.start.x == wall.start.x;
+            if (both_horizontal || both_vertical) && last.end.distance(wall.start) < 1e-3 {
+                last.end = wall.end;
+                continue;
+            }
+        }
+        merged.push(wall);
+    }
+    merged
+}
+
+/// A perfect maze carved by a randomised depth-first search, so every cell is reachable
+/// by exactly one route. The origin sits in the middle of a cell.
+fn generate_maze(config: &WorldGenConfig) -> Vec<(Vec2, Vec3)> {
+    let mut rng = config.rng();
+
+    // An odd number of cells puts a cell centre on the origin
+    let mut cells = ((config.bounds * 2.0 / MAZE_CELL_SIZE) as usize).max(1);
+    if cells % 2 == 0 {
+        cells -= 1;
+    }
+    let origin = -(cells as f32) * MAZE_CELL_SIZE / 2.0;
+    let corner = |x: usize, z: usize| {
+        Vec2::new(
+            origin + x as f32 * MAZE_CELL_SIZE,
+            origin + z as f32 * MAZE_CELL_SIZE,
+        )
+    };
+
+    // Walls on the east and south side of every cell
+    let mut east = vec![vec![true; cells]; cells];
+    let mut south = vec![vec![true; cells]; cells];
+    let mut visited = vec![vec![false; cells]; cells];
+
+    let mut stack = vec![(0, 0)];
+    visited[0][0] = true;
+    while let Some(&(x, z)) = stack.last() {
+        let mut neighbours = Vec::new();
+        if x > 0 && !visited[x - 1][z] {
+            neighbours.push((x - 1, z));
+        }
+        if x + 1 < cells && !visited[x + 1][z] {
+            neighbours.push((x + 1, z));
+        }
+        if z > 0 && !visited[x][z - 1] {
+            neighbours.push((x, z - 1));
+        }
+        if z + 1 < cells && !visited[x][z + 1] {
+            neighbours.push((x, z + 1));
+        }
+
+        if neighbours.is_empty() {
+            stack.pop();
+            continue;
+        }
+
+        let (nx, nz) = neighbours[rng.gen_range(0..neighbours.len())];
+        match (nx.cmp(&x), nz.cmp(&z)) {
+            (std::cmp::Ordering::Greater, _) => east[x][z] = false,
+            (std::cmp::Ordering::Less, _) => east[nx][nz] = false,
+            (_, std::cmp::Ordering::Greater) => south[x][z] = false,
+            _ => south[nx][nz] = false,
+        }
+        visited[nx][nz] = true;
+        stack.push((nx, nz));
+    }
+
+    let mut walls = Vec::new();
+    for i in 0..cells {
+        // The outer boundary on the west and north
+        walls.push(Wall {
+            start: corner(0, i),
+            end: corner(0, i + 1),
+        });
+        walls.push(Wall {
+            start: corner(i, 0),
+            end: corner(i + 1, 0),
+        });
+    }
+    for x in 0..cells {
+        for z in 0..cells {
+            if east[x][z] {
+                walls.push(Wall {
+                    start: corner(x + 1, z),
+                    end: corner(x + 1, z + 1),
+                });
+            }
+            if south[x][z] {
+                walls.push(Wall {
+                    start: corner(x, z + 1),
+                    end: corner(x + 1, z + 1),
+                });
+            }
+        }
+    }
+
+    // Zones are cleared one cell side at a time, before the runs are merged
+    walls.retain(|wall| {
+        let (center, size) = wall.to_box();
+        clear_of_zones(center, size, config)
+    });
+
+    merge_walls(walls).iter().map(Wall::to_box).collect()
+}
+
+/// An axis-aligned area on the ground plane (x, z).
+#[derive(Clone, Copy)]
+struct Area {
+    min: Vec2,
+    max: Vec2,
+}
+
+/// Rooms made by binary space partitioning: each split is a wall with one doorway,
+/// which keeps every room connected to the rest.
+fn generate_rooms(config: &WorldGenConfig) -> Vec<(Vec2, Vec3)> {
+    let mut rng = config.rng();
+    let bounds = Area {
+        min: Vec2::splat(-config.bounds),
+        max: Vec2::splat(config.bounds),
+    };
+
+    let mut walls = vec![
+        Wall {
+            start: bounds.min,
+            end: Vec2::new(bounds.max.x, bounds.min.y),
+        },
+        Wall {
+            start: Vec2::new(bounds.min.x, bounds.max.y),
+            end: bounds.max,
+        },
+        Wall {
+            start: bounds.min,
+            end: Vec2::new(bounds.min.x, bounds.max.y),
+        },
+        Wall {
+            start: Vec2::new(bounds.max.x, bounds.min.y),
+            end: bounds.max,
+        },
+    ];
+    let mut doors: Vec<Vec2> = Vec::new();
+    let mut areas = vec![bounds];
+
+    while let Some(area) = areas.pop() {
+        let size = area.max - area.min;
+        if size.x < MIN_ROOM_SIZE * 2.0 && size.y < MIN_ROOM_SIZE * 2.0 {
+            continue;
+        }
+        let vertical = if size.x < MIN_ROOM_SIZE * 2.0 {
+            false
+        } else if size.y < MIN_ROOM_SIZE * 2.0 {
+            true
+        } else {
+            rng.gen_bool(0.5)
+        };
+
+        // A split that ends inside a doorway would block it
+        let keep_off = DOOR_WIDTH / 2.0 + WALL_THICKNESS + config.agent_buffer * 2.0;
+        let split = (0..SPLIT_ATTEMPTS).find_map(|_| {
+            let (low, high) = if vertical {
+                (area.min.x, area.max.x)
+            } else {
+                (area.min.y, area.max.y)
+            };
+            let at = rng.gen_range(low + MIN_ROOM_SIZE..=high - MIN_ROOM_SIZE);
+            let (start, end) = if vertical {
+                (Vec2::new(at, area.min.y), Vec2::new(at, area.max.y))
+            } else {
+                (Vec2::new(area.min.x, at), Vec2::new(area.max.x, at))
+            };
+            doors
+                .iter()
+                .all(|door| door.distance(start) > keep_off && door.distance(end) > keep_off)
+                .then_some((at, start, end))
+        });
+        let Some((at, start, end)) = split else {
+            continue;
+        };
+
+        // Leave a doorway somewhere along the new wall
+        let length = start.distance(end);
+        let direction = (end - start) / length;
+        let door_at = rng.gen_range(DOOR_WIDTH..length - DOOR_WIDTH);
+        let door = start + direction * door_at;
+        doors.push(door);
+        walls.push(Wall {
+            start,
+            end: door - direction * DOOR_WIDTH / 2.0,
+        });
+        walls.push(Wall {
+            start: door + direction * DOOR_WIDTH / 2.0,
+            end,
+        });
+
+        if vertical {
+            areas.push(Area {
+                min: area.min,
+                max: Vec2::new(at, area.max.y),
+            });
+            areas.push(Area {
+                min: Vec2::new(at, area.min.y),
+                max: area.max,
+            });
+        } else {
+            areas.push(Area {
+                min: area.min,
+                max: Vec2::new(area.max.x, at),
+            });
+            areas.push(Area {
+                min: Vec2::new(area.min.x, at),
+                max: area.max,
+            });
+        }
+    }
+
+    walls
+        .iter()
+        .map(Wall::to_box)
+        .filter(|(center, size)| clear_of_zones(*center, *size, config))
+        .collect()
+}
+
+/// City blocks separated by wide streets, each divided into buildings by narrow alleys.
+/// The streets cross at the origin.
+fn generate_city(config: &WorldGenConfig) -> Vec<(Vec2, Vec3)> {
+    let mut rng = config.rng();
+    let pitch = BLOCK_SIZE + STREET_WIDTH;
+    let blocks_per_side = ((config.bounds / pitch) as i32).max(1);
+
+    let mut boxes = Vec::new();
+    for block_x in -blocks_per_side..blocks_per_side {
+        for block_z in -blocks_per_side..blocks_per_side {
+            let block_min = Vec2::new(
+                block_x as f32 * pitch + STREET_WIDTH / 2.0,
+                block_z as f32 * pitch + STREET_WIDTH / 2.0,
+            );
+            add_block_buildings(&mut rng, block_min, &mut boxes);
+        }
+    }
+
+    boxes.retain(|(center, size)| clear_of_zones(*center, *size, config));
+    boxes
+}
+
+fn add_block_buildings(rng: &mut StdRng, block_min: Vec2, boxes: &mut Vec<(Vec2, Vec3)>) {
+    let lots_x = rng.gen_range(1..=3);
+    let lots_z = rng.gen_range(1..=3);
+    let lot_size = Vec2::new(
+        (BLOCK_SIZE - ALLEY_WIDTH * (lots_x - 1) as f32) / lots_x as f32,
+        (BLOCK_SIZE - ALLEY_WIDTH * (lots_z - 1) as f32) / lots_z as f32,
+    );
+
+    for x in 0..lots_x {
+        for z in 0..lots_z {
+            if rng.gen_bool(EMPTY_LOT_CHANCE) {
+                continue;
+            }
+            let lot_min = block_min
+                + Vec2::new(
+                    x as f32 * (lot_size.x + ALLEY_WIDTH),
+                    z as f32 * (lot_size.y + ALLEY_WIDTH),
+                );
+            let height = rng.gen_range(1.0..4.0);
+            boxes.push((
+                lot_min + lot_size / 2.0,
+                Vec3::new(lot_size.x, height, lot_size.y),
+            ));
+        }
+    }
+}
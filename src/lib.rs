@@ -0,0 +1,245 @@
+pub mod camera;
+pub mod command_queue;
+pub mod cursor;
+pub mod level;
+pub mod level_asset;
+pub mod level_generators;
+pub mod movingai;
+pub mod obstacle_editor;
+pub mod obstacles;
+pub mod optimality;
+pub mod path_validation;
+pub mod pathfinding;
+pub mod pathfinding_diagnostics;
+pub mod player;
+pub mod player_gizmos;
+pub mod player_movement;
+pub mod player_stats;
+pub mod polygon_tool;
+pub mod pursue;
+pub mod search_debug;
+pub mod search_stepper;
+pub mod simulation;
+pub mod stuck_detection;
+
+pub use player::*;
+pub use player_gizmos::*;
+pub use player_movement::*;
+pub use player_stats::*;
+pub mod utils;
+pub mod wanderer;
+
+use crate::obstacles::AGENT_BUFFER;
+use crate::pathfinding::{NavMesh, Planner};
+use crate::utils::Join;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use obstacles::ObstaclePolygons;
+use pathfinding_diagnostics::PathfindingDiagnosticsPlugin;
+
+#[derive(Component)]
+pub struct Ground;
+
+/// Width and depth of the ground plane mesh, before any level rescales it.
+pub const GROUND_SIZE: f32 = 120.0;
+
+/// The stages the pathfinding systems run in, in this order, every `Update`. Systems of
+/// your own can be ordered against them, for example to issue orders before `Planning`.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PathfindingSet {
+    /// Mouse and keyboard: player orders and level editing.
+    Input,
+    /// Rebuilds the obstacle polygons and nav mesh from the obstacle entities.
+    Obstacles,
+    /// Plans, validates and repairs agent paths.
+    Planning,
+    /// Moves agents along their paths.
+    Movement,
+}
+
+/// Settings of the `PathfindingPlugin`, available as a resource while the app runs.
+/// Changing `planner`, `agent_buffer` or `corner_join` there rebuilds the nav data.
+#[derive(Resource, Debug, Clone)]
+pub struct PathfindingConfig {
+    pub planner: Planner,
+    /// How far obstacle footprints are inflated to keep agents clear of them.
+    pub agent_buffer: f32,
+    /// How inflated footprints are joined around corners.
+    pub corner_join: Join,
+    /// Adds the click, keyboard and pursue orders for the `Player`.
+    pub player_input: bool,
+    /// Adds the obstacle editor, polygon tool and level save and load keys.
+    pub level_editing: bool,
+}
+
+impl Default for PathfindingConfig {
+    fn default() -> Self {
+        PathfindingConfig {
+            planner: Planner::default(),
+            agent_buffer: AGENT_BUFFER,
+            corner_join: Join::default(),
+            player_input: true,
+            level_editing: true,
+        }
+    }
+}
+
+/// Obstacle polygons and the nav mesh derived from the obstacle entities
+/// (`CuboidObstacle`, `PolygonObstacle`, `PrimitiveObstacle`, `NavObstacle` and
+/// `WalkableArea`), path planning, validation and stall recovery for every entity with
+/// a `TargetPosition`, and movement along those paths.
+#[derive(Default)]
+pub struct PathfindingPlugin {
+    pub config: PathfindingConfig,
+}
+
+impl Plugin for PathfindingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(PathfindingDiagnosticsPlugin)
+            .insert_resource(self.config.clone())
+            .init_resource::<ObstaclePolygons>()
+            .insert_resource(NavMesh::new().with_planner(self.config.planner))
+            .init_resource::<level::BakedNavMesh>()
+            .init_resource::<path_validation::PathValidationTimer>()
+            .add_event::<path_validation::ObstaclesChanged>()
+            .add_event::<path_validation::PathInvalidated>()
+            .add_event::<path_validation::RepathAllAgents>()
+            .add_event::<stuck_detection::PathFailed>()
+            .configure_sets(
+                Update,
+                (
+                    PathfindingSet::Input.run_if(any_with_component::<PrimaryWindow>),
+                    PathfindingSet::Obstacles,
+                    PathfindingSet::Planning,
+                    PathfindingSet::Movement,
+                )
+                    .chain(),
+            )
+            .add_systems(
+                Update,
+                obstacles::update_nav_obstacle_footprints
+                    .before(obstacles::rebuild_obstacles)
+                    .in_set(PathfindingSet::Obstacles),
+            )
+            .add_systems(
+                Update,
+                (
+                    obstacles::rebuild_obstacles,
+                    level::apply_baked_nav_mesh,
+                    path_validation::repath_all_agents,
+                )
+                    .chain()
+                    .in_set(PathfindingSet::Obstacles),
+            )
+            .add_systems(
+                Update,
+                (
+                    command_queue::advance_command_queue,
+                    pursue::pursue_target,
+                    pursue::track_velocity,
+                    path_validation::validate_paths,
+                    path_validation::report_invalidated_paths,
+                    stuck_detection::monitor_path_progress,
+                    stuck_detection::report_failed_paths,
+                )
+                    .in_set(PathfindingSet::Planning),
+            )
+            .add_systems(
+                Update,
+                player::move_player_towards_target.in_set(PathfindingSet::Movement),
+            );
+
+        if self.config.player_input {
+            app.init_resource::<cursor::CursorPosition>().add_systems(
+                Update,
+                (
+                    cursor::draw_cursor,
+                    player::handle_right_click_set_target_position,
+                    command_queue::handle_shift_right_click_queue_target,
+                    command_queue::toggle_patrol,
+                    pursue::handle_pursue_order,
+                    player_movement::move_player_with_wasd,
+                )
+                    .in_set(PathfindingSet::Input),
+            );
+        }
+
+        if self.config.level_editing {
+            app.add_plugins(level_asset::NavLevelPlugin)
+                .init_resource::<cursor::CursorPosition>()
+                .init_resource::<obstacle_editor::ObstacleEditor>()
+                .init_resource::<obstacle_editor::EditorRng>()
+                .init_resource::<polygon_tool::PolygonTool>()
+                .add_systems(
+                    Update,
+                    (
+                        obstacle_editor::toggle_obstacle_editor,
+                        obstacle_editor::edit_obstacles,
+                        obstacle_editor::draw_obstacle_editor,
+                        polygon_tool::toggle_polygon_tool,
+                        polygon_tool::draw_polygon_obstacles,
+                        polygon_tool::draw_polygon_tool,
+                        level::save_level,
+                    )
+                        .chain()
+                        .in_set(PathfindingSet::Input),
+                )
+                .add_systems(
+                    Update,
+                    level_asset::load_level
+                        .before(obstacles::rebuild_obstacles)
+                        .in_set(PathfindingSet::Obstacles),
+                );
+        }
+    }
+}
+
+/// The player-following camera: Y toggles following, the mouse wheel zooms and, while
+/// not following, moving the cursor to the window edge pans.
+pub struct CameraControlPlugin;
+
+impl Plugin for CameraControlPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(camera::CameraFollowToggle(true))
+            .insert_resource(camera::CameraZoom(10.0))
+            .add_systems(
+                Update,
+                (
+                    camera::camera_follow,
+                    camera::toggle_camera_follow,
+                    camera::camera_edge_pan,
+                    camera::camera_zoom,
+                )
+                    .after(PathfindingSet::Movement),
+            );
+    }
+}
+
+/// Path gizmos, nav mesh vertex markers, the walkable area outline, the search debug
+/// overlay (F1-F3), the optimality check (F4) and the Theta* step debugger (F5-F8).
+pub struct DebugGizmosPlugin;
+
+impl Plugin for DebugGizmosPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<cursor::CursorPosition>()
+            .init_resource::<search_debug::SearchDebug>()
+            .init_resource::<search_stepper::SearchStepper>()
+            .add_systems(Startup, search_stepper::setup_stepper_panel)
+            .add_systems(
+                Update,
+                (
+                    draw_path_gizmos,
+                    obstacles::refresh_nav_vertex_markers,
+                    obstacles::draw_walkable_area,
+                    search_debug::toggle_search_debug,
+                    optimality::run_optimality_check,
+                    search_debug::rebuild_visibility_graph,
+                    search_debug::draw_search_debug,
+                    search_stepper::handle_stepper_input,
+                    search_stepper::update_stepper_panel,
+                    search_stepper::draw_stepper_gizmos,
+                )
+                    .after(PathfindingSet::Movement),
+            );
+    }
+}
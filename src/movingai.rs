@@ -0,0 +1,454 @@
+use crate::obstacles::ObstaclePolygons;
+use crate::pathfinding::{find_path, path_length, NavMesh, PathQueryStats, Planner};
+use crate::utils::{point_in_polygon, Join, Polygon};
+use bevy::math::Vec3;
+use std::fmt;
+use std::path::Path;
+
+/// How far blocked rectangles are grown, so that cells touching only at a corner block
+/// the diagonal between them, as they do in the benchmark's reference paths.
+const CORNER_SEAL: f32 = 1e-3;
+/// A path this much longer than the reference counts as longer.
+const LENGTH_TOLERANCE: f64 = 1e-4;
+
+#[derive(Debug)]
+pub enum MovingAiError {
+    Io(std::io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for MovingAiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MovingAiError::Io(error) => write!(f, "{error}"),
+            MovingAiError::Parse { line, message } => write!(f, "line {line}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for MovingAiError {}
+
+fn parse_error(line: usize, message: impl Into<String>) -> MovingAiError {
+    MovingAiError::Parse {
+        line,
+        message: message.into(),
+    }
+}
+
+/// A Moving AI Lab `.map` grid. Cell (x, y) covers x..x+1 and y..y+1 on the ground
+/// plane, with y along z.
+#[derive(Debug, Clone)]
+pub struct GridMap {
+    pub width: usize,
+    pub height: usize,
+    passable: Vec<bool>,
+}
+
+impl GridMap {
+    /// Parses the header (`type`, `height`, `width`, `map`) and the rows. Ground (`.`,
+    /// `G`) and swamp (`S`) are passable; trees, water and out of bounds (`T`, `W`,
+    /// `@`, `O`) are not.
+    pub fn parse(text: &str) -> Result<Self, MovingAiError> {
+        let mut lines = text.lines().enumerate();
+        let (mut width, mut height) = (None, None);
+        for (index, line) in lines.by_ref() {
+            let mut fields = line.split_whitespace();
+            match (fields.next(), fields.next()) {
+                (Some("type"), _) | (None, _) => {}
+                (Some("height"), Some(value)) => {
+                    height = Some(
+                        value
+                            .parse()
+                            .map_err(|_| parse_error(index + 1, "bad height"))?,
+                    )
+                }
+                (Some("width"), Some(value)) => {
+                    width = Some(
+                        value
+                            .parse()
+                            .map_err(|_| parse_error(index + 1, "bad width"))?,
+                    )
+                }
+                (Some("map"), None) => break,
+                _ => return Err(parse_error(index + 1, format!("unexpected `{line}`"))),
+            }
+        }
+        let (Some(width), Some(height)) = (width, height) else {
+            return Err(parse_error(0, "missing width or height"));
+        };
+
+        let mut passable = Vec::with_capacity(width * height);
+        for (index, line) in lines.take(height) {
+            let row: Vec<bool> = line
+                .trim_end()
+                .chars()
+                .map(|cell| matches!(cell, '.' | 'G' | 'S'))
+                .collect();
+            if row.len() != width {
+                return Err(parse_error(index + 1, format!("expected {width} cells")));
+            }
+            passable.extend(row);
+        }
+        if passable.len() != width * height {
+            return Err(parse_error(0, format!("expected {height} rows")));
+        }
+
+        Ok(GridMap {
+            width,
+            height,
+            passable,
+        })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MovingAiError> {
+        GridMap::parse(&std::fs::read_to_string(path).map_err(MovingAiError::Io)?)
+    }
+
+    /// Whether the cell is inside the map and passable.
+    pub fn is_passable(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.passable[y * self.width + x]
+    }
+
+    /// The blocked cells merged into as few rectangles as a greedy sweep finds: runs
+    /// along each row, grown downwards while the rows below repeat them. The map's
+    /// outline is the walkable boundary, so nothing leaves the grid.
+    pub fn obstacle_polygons(&self) -> ObstaclePolygons {
+        let mut covered = vec![false; self.width * self.height];
+        let mut polygons = ObstaclePolygons::new();
+
+        let (width, height) = (self.width as f32, self.height as f32);
+        let mut boundary = Polygon::new();
+        boundary.add_vertex(0.0, 0.0, 0.0);
+        boundary.add_vertex(width, 0.0, 0.0);
+        boundary.add_vertex(width, 0.0, height);
+        boundary.add_vertex(0.0, 0.0, height);
+        boundary.set_clockwise(false);
+        polygons.add_polygon(boundary);
+
+        for y in 0..self.height {
+            let mut x = 0;
+            while x < self.width {
+                if self.is_passable(x, y) || covered[y * self.width + x] {
+                    x += 1;
+                    continue;
+                }
+
+                let free = |x: usize, y: usize, covered: &[bool]| {
+                    !self.is_passable(x, y) && !covered[y * self.width + x]
+                };
+                let mut end = x + 1;
+                while end < self.width && free(end, y, &covered) {
+                    end += 1;
+                }
+                let mut bottom = y + 1;
+                while bottom < self.height && (x..end).all(|cell| free(cell, bottom, &covered)) {
+                    bottom += 1;
+                }
+                for row in y..bottom {
+                    covered[row * self.width + x..row * self.width + end].fill(true);
+                }
+
+                let mut rectangle = Polygon::new();
+                rectangle.add_vertex(x as f32, 0.0, y as f32);
+                rectangle.add_vertex(x as f32, 0.0, bottom as f32);
+                rectangle.add_vertex(end as f32, 0.0, bottom as f32);
+                rectangle.add_vertex(end as f32, 0.0, y as f32);
+                polygons.add_polygon(rectangle.offset(CORNER_SEAL, Join::default()));
+                x = end;
+            }
+        }
+        polygons
+    }
+
+    /// The nav mesh of the merged rectangles, without the corners that end up inside a
+    /// neighbouring rectangle or outside the map. A corner is not tested against its own
+    /// rectangle, whose boundary it lies on.
+    pub fn nav_mesh(&self, obstacle_polygons: &ObstaclePolygons) -> NavMesh {
+        let mut nav_mesh = NavMesh::from_polygons(obstacle_polygons);
+        nav_mesh.vertices.retain(|vertex| {
+            !obstacle_polygons.polygons.iter().any(|polygon| {
+                !polygon.vertices.contains(vertex)
+                    && point_in_polygon(vertex, polygon) != polygon.is_walkable_boundary()
+            })
+        });
+        nav_mesh
+    }
+}
+
+/// The centre of a cell on the ground plane.
+pub fn cell_center(x: usize, y: usize) -> Vec3 {
+    Vec3::new(x as f32 + 0.5, 0.0, y as f32 + 0.5)
+}
+
+/// One line of a `.scen` file: a query on a map and the length of its optimal octile
+/// path (8-connected, diagonals of length √2, no cutting corners).
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    pub bucket: u32,
+    pub map: String,
+    pub start: (usize, usize),
+    pub goal: (usize, usize),
+    pub optimal_length: f64,
+}
+
+/// Parses a version 1 `.scen` file.
+pub fn parse_scenarios(text: &str) -> Result<Vec<Scenario>, MovingAiError> {
+    let mut scenarios = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with("version") {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 9 {
+            return Err(parse_error(index + 1, "expected 9 fields"));
+        }
+        let number = |field: usize| {
+            fields[field]
+                .trim()
+                .parse::<usize>()
+                .map_err(|_| parse_error(index + 1, format!("bad field {}", field + 1)))
+        };
+        scenarios.push(Scenario {
+            bucket: number(0)? as u32,
+            map: fields[1].to_string(),
+            start: (number(4)?, number(5)?),
+            goal: (number(6)?, number(7)?),
+            optimal_length: fields[8]
+                .trim()
+                .parse()
+                .map_err(|_| parse_error(index + 1, "bad optimal length"))?,
+        });
+    }
+    Ok(scenarios)
+}
+
+pub fn load_scenarios(path: impl AsRef<Path>) -> Result<Vec<Scenario>, MovingAiError> {
+    parse_scenarios(&std::fs::read_to_string(path).map_err(MovingAiError::Io)?)
+}
+
+/// How one planner did on a set of scenarios, against the reference lengths.
+#[derive(Debug, Clone)]
+pub struct BenchmarkResult {
+    pub planner: Planner,
+    pub scenarios: usize,
+    /// Scenarios whose start or goal is blocked, which were not run.
+    pub skipped: usize,
+    /// Scenarios for which no path was found.
+    pub unsolved: usize,
+    /// Path length over the reference length, per solved scenario.
+    pub ratios: Vec<f64>,
+    pub query_stats: PathQueryStats,
+}
+
+impl BenchmarkResult {
+    /// Solved scenarios whose path is longer than the reference. Any-angle paths are
+    /// normally shorter than the octile reference, so these deserve a look.
+    pub fn longer_than_reference(&self) -> usize {
+        self.ratios
+            .iter()
+            .filter(|&&ratio| ratio > 1.0 + LENGTH_TOLERANCE)
+            .count()
+    }
+}
+
+impl fmt::Display for BenchmarkResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let solved = self.ratios.len();
+        let mean = self.ratios.iter().sum::<f64>() / solved.max(1) as f64;
+        let min = self.ratios.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = self
+            .ratios
+            .iter()
+            .copied()
+            .fold(f64::NEG_INFINITY, f64::max);
+        let run = self.scenarios - self.skipped;
+        let queries = self.query_stats.queries.max(1) as f64;
+        write!(
+            f,
+            "{:?}: solved {solved}/{run} ({} skipped, {} unsolved), length / reference \
+             mean {mean:.4} (min {:.4}, max {:.4}), {} longer than reference, {:.2} ms and \
+             {:.0} nodes expanded per query",
+            self.planner,
+            self.skipped,
+            self.unsolved,
+            if solved > 0 { min } else { 0.0 },
+            if solved > 0 { max } else { 0.0 },
+            self.longer_than_reference(),
+            self.query_stats.query_time.as_secs_f64() * 1000.0 / queries,
+            self.query_stats.search.nodes_expanded as f64 / queries
+        )
+    }
+}
+
+/// Runs every scenario through `find_path` with the planner and compares the lengths
+/// with the reference ones.
+pub fn run_scenarios(map: &GridMap, scenarios: &[Scenario], planner: Planner) -> BenchmarkResult {
+    let obstacle_polygons = map.obstacle_polygons();
+    let nav_mesh = map.nav_mesh(&obstacle_polygons).with_planner(planner);
+
+    let mut result = BenchmarkResult {
+        planner,
+        scenarios: scenarios.len(),
+        skipped: 0,
+        unsolved: 0,
+        ratios: Vec::new(),
+        query_stats: PathQueryStats::default(),
+    };
+    for scenario in scenarios {
+        let (start, goal) = (scenario.start, scenario.goal);
+        if !map.is_passable(start.0, start.1) || !map.is_passable(goal.0, goal.1) {
+            result.skipped += 1;
+            continue;
+        }
+
+        let path = find_path(
+            &nav_mesh,
+            &obstacle_polygons,
+            cell_center(start.0, start.1),
+            cell_center(goal.0, goal.1),
+            &mut result.query_stats,
+        );
+
+        match path {
+            Some(waypoints) => {
+                // Direct paths leave out their start, searched ones repeat it
+                let mut points = vec![cell_center(start.0, start.1)];
+                points.extend(waypoints);
+                let length = path_length(&points) as f64;
+                let ratio = if scenario.optimal_length > 0.0 {
+                    length / scenario.optimal_length
+                } else {
+                    1.0
+                };
+                result.ratios.push(ratio);
+            }
+            None => result.unsolved += 1,
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP: &str = "type octile\nheight 3\nwidth 4\nmap\n@@..\n@@.T\n....\n";
+
+    #[test]
+    fn parses_a_map() {
+        let map = GridMap::parse(MAP).unwrap();
+        assert_eq!((map.width, map.height), (4, 3));
+        assert!(!map.is_passable(0, 0));
+        assert!(map.is_passable(2, 0));
+        assert!(!map.is_passable(3, 1));
+        assert!(map.is_passable(0, 2));
+        assert!(!map.is_passable(4, 0));
+        assert!(!map.is_passable(0, 3));
+    }
+
+    #[test]
+    fn rejects_malformed_maps() {
+        let short_row = "type octile\nheight 2\nwidth 3\nmap\n...\n..\n";
+        assert!(matches!(
+            GridMap::parse(short_row),
+            Err(MovingAiError::Parse { line: 6, .. })
+        ));
+        let missing_row = "type octile\nheight 2\nwidth 3\nmap\n...\n";
+        assert!(matches!(
+            GridMap::parse(missing_row),
+            Err(MovingAiError::Parse { line: 0, .. })
+        ));
+        assert!(GridMap::parse("type octile\nwidth 3\nmap\n...\n").is_err());
+        assert!(GridMap::parse("type octile\nheight x\nwidth 3\nmap\n").is_err());
+    }
+
+    #[test]
+    fn parses_scenarios() {
+        let text = "version 1\n\
+                    0\tmaze.map\t4\t3\t2\t0\t0\t2\t2.82842712\n\
+                    \n\
+                    3 maze.map 4 3 3 2 2 0 2.41421356\n";
+        let scenarios = parse_scenarios(text).unwrap();
+        assert_eq!(scenarios.len(), 2);
+        assert_eq!(scenarios[0].map, "maze.map");
+        assert_eq!((scenarios[0].start, scenarios[0].goal), ((2, 0), (0, 2)));
+        assert_eq!(scenarios[1].bucket, 3);
+        assert!((scenarios[1].optimal_length - 2.41421356).abs() < 1e-9);
+
+        assert!(matches!(
+            parse_scenarios("version 1\n0 maze.map 4 3 2 0 0 2\n"),
+            Err(MovingAiError::Parse { line: 2, .. })
+        ));
+        assert!(matches!(
+            parse_scenarios("0 maze.map 4 3 2 x 0 2 1.0\n"),
+            Err(MovingAiError::Parse { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn merges_blocked_cells_into_rectangles() {
+        let map = GridMap::parse(MAP).unwrap();
+        let polygons = map.obstacle_polygons();
+        // The boundary, the 2 by 2 block and the single tree
+        assert_eq!(polygons.polygons.len(), 3);
+        assert!(polygons.polygons[0].is_walkable_boundary());
+        let areas: Vec<f32> = polygons.polygons[1..]
+            .iter()
+            .map(|polygon| polygon.area().round())
+            .collect();
+        assert_eq!(areas, vec![4.0, 1.0]);
+
+        let columns = GridMap::parse("type octile\nheight 2\nwidth 3\nmap\n@.@\n@.@\n").unwrap();
+        assert_eq!(columns.obstacle_polygons().polygons.len(), 3);
+    }
+
+    #[test]
+    fn the_map_outline_bounds_the_walkable_space() {
+        let map = GridMap::parse(MAP).unwrap();
+        let polygons = map.obstacle_polygons();
+        for y in 0..map.height {
+            for x in 0..map.width {
+                assert_eq!(
+                    polygons.contains_point(cell_center(x, y)),
+                    !map.is_passable(x, y),
+                    "({x}, {y})"
+                );
+            }
+        }
+        assert!(polygons.contains_point(Vec3::new(2.5, 0.0, -0.5)));
+        assert!(polygons.contains_point(Vec3::new(4.5, 0.0, 2.5)));
+    }
+
+    #[test]
+    fn any_angle_paths_beat_the_octile_reference() {
+        let map = GridMap::parse(MAP).unwrap();
+        // Around the corner of the block and around the tree, both 4 octile moves
+        // without cutting corners; the first query starts on the block and is skipped
+        let scenarios = parse_scenarios(
+            "version 1\n\
+             0 tiny.map 4 3 0 0 2 2 2.82842712\n\
+             0 tiny.map 4 3 2 0 0 2 4\n\
+             0 tiny.map 4 3 3 0 3 2 4\n",
+        )
+        .unwrap();
+        let around_block = 2.0 * (0.5f64.powi(2) + 1.5f64.powi(2)).sqrt();
+        let around_tree = 1.0 + 2.0 * 0.5f64.sqrt();
+
+        for planner in [Planner::ThetaStar, Planner::AStar, Planner::Exact] {
+            let result = run_scenarios(&map, &scenarios, planner);
+            assert_eq!((result.scenarios, result.skipped), (3, 1));
+            assert_eq!(result.unsolved, 0);
+            assert_eq!(result.ratios.len(), 2);
+            assert!(
+                (result.ratios[0] - around_block / 4.0).abs() < 1e-2,
+                "{result}"
+            );
+            assert!(
+                (result.ratios[1] - around_tree / 4.0).abs() < 1e-2,
+                "{result}"
+            );
+            assert_eq!(result.longer_than_reference(), 0);
+            assert!(result.to_string().contains("(1 skipped, 0 unsolved)"));
+        }
+    }
+}
@@ -0,0 +1,263 @@
+use crate::cursor::CursorPosition;
+use crate::obstacles::{spawn_cuboid, CuboidObstacle};
+use crate::polygon_tool::PolygonTool;
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Size of the mesh of a freshly placed cuboid.
+const NEW_CUBOID_SIZE: Vec3 = Vec3::new(1.5, 1.0, 1.5);
+/// How far the rotate handle sits beyond the side of the selected cuboid.
+const ROTATE_HANDLE_OFFSET: f32 = 1.0;
+/// How close the cursor has to be to a handle to grab it.
+const HANDLE_RADIUS: f32 = 0.4;
+/// The smallest scale a cuboid can be shrunk to.
+const MIN_SCALE: f32 = 0.2;
+
+/// What the editor is doing with the selected cuboid while the mouse button is held.
+enum EditorDrag {
+    Move { offset: Vec3 },
+    Rotate,
+    Scale,
+}
+
+/// In-game obstacle editor, toggled with E. Left-click on empty ground places a cuboid,
+/// on a cuboid selects and drags it. The selected cuboid has a rotate handle (yellow)
+/// and a scale handle (cyan) and is removed with Delete.
+#[derive(Resource, Default)]
+pub struct ObstacleEditor {
+    pub active: bool,
+    selected: Option<Entity>,
+    drag: Option<EditorDrag>,
+}
+
+/// Colours the cuboids the editor places. Seed it from `WorldGenConfig::detail_rng`
+/// so that a session with the same seed places the same colours.
+#[derive(Resource)]
+pub struct EditorRng(pub StdRng);
+
+impl Default for EditorRng {
+    fn default() -> Self {
+        EditorRng(StdRng::seed_from_u64(0))
+    }
+}
+
+/// Half the extent of a cuboid's footprint along its local x and z axes.
+fn half_extents(transform: &Transform, cuboid: &CuboidObstacle) -> Vec2 {
+    let extents = cuboid.size * transform.scale / 2.0;
+    Vec2::new(extents.x, extents.z)
+}
+
+fn rotate_handle(transform: &Transform, cuboid: &CuboidObstacle) -> Vec3 {
+    let half = half_extents(transform, cuboid);
+    transform.translation + transform.rotation * Vec3::X * (half.x + ROTATE_HANDLE_OFFSET)
+}
+
+fn scale_handle(transform: &Transform, cuboid: &CuboidObstacle) -> Vec3 {
+    let half = half_extents(transform, cuboid);
+    transform.translation + transform.rotation * Vec3::new(half.x, 0.0, half.y)
+}
+
+/// The point relative to the cuboid's centre, in its unrotated frame.
+fn to_local(transform: &Transform, point: Vec3) -> Vec3 {
+    transform.rotation.inverse() * (point - transform.translation)
+}
+
+fn footprint_contains(transform: &Transform, cuboid: &CuboidObstacle, point: Vec3) -> bool {
+    let half = half_extents(transform, cuboid);
+    let local = to_local(transform, point);
+    local.x.abs() <= half.x && local.z.abs() <= half.y
+}
+
+fn near(a: Vec3, b: Vec3) -> bool {
+    Vec2::new(a.x, a.z).distance(Vec2::new(b.x, b.z)) <= HANDLE_RADIUS
+}
+
+pub fn toggle_obstacle_editor(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut editor: ResMut<ObstacleEditor>,
+    mut polygon_tool: ResMut<PolygonTool>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyE) {
+        editor.active = !editor.active;
+        editor.selected = None;
+        editor.drag = None;
+        if editor.active {
+            polygon_tool.active = false;
+        }
+        println!(
+            "Obstacle editor {}",
+            if editor.active { "enabled" } else { "disabled" }
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn edit_obstacles(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    cursor_position: Res<CursorPosition>,
+    mut editor: ResMut<ObstacleEditor>,
+    mut rng: ResMut<EditorRng>,
+    mut cuboid_query: Query<(Entity, &mut Transform, &CuboidObstacle)>,
+) {
+    if !editor.active {
+        return;
+    }
+
+    // Forget a selection that was despawned from elsewhere
+    if let Some(selected) = editor.selected {
+        if cuboid_query.get(selected).is_err() {
+            editor.selected = None;
+            editor.drag = None;
+        }
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Delete) {
+        if let Some(selected) = editor.selected.take() {
+            commands.entity(selected).despawn();
+            editor.drag = None;
+        }
+        return;
+    }
+
+    if mouse_button_input.just_released(MouseButton::Left) {
+        editor.drag = None;
+    }
+
+    let Some(cursor) = cursor_position.0 else {
+        return;
+    };
+
+    if mouse_button_input.just_pressed(MouseButton::Left) {
+        // Handles of the current selection take priority over everything under them
+        if let Some((_, transform, cuboid)) = editor
+            .selected
+            .and_then(|entity| cuboid_query.get(entity).ok())
+        {
+            if near(cursor, rotate_handle(transform, cuboid)) {
+                editor.drag = Some(EditorDrag::Rotate);
+                return;
+            }
+            if near(cursor, scale_handle(transform, cuboid)) {
+                editor.drag = Some(EditorDrag::Scale);
+                return;
+            }
+        }
+
+        if let Some((entity, transform, _)) = cuboid_query
+            .iter()
+            .find(|(_, transform, cuboid)| footprint_contains(transform, cuboid, cursor))
+        {
+            editor.selected = Some(entity);
+            editor.drag = Some(EditorDrag::Move {
+                offset: transform.translation - cursor,
+            });
+            return;
+        }
+
+        let rng = &mut rng.0;
+        let color = Color::srgb(rng.gen::<f32>(), rng.gen::<f32>(), rng.gen::<f32>());
+        let transform = Transform::from_xyz(cursor.x, NEW_CUBOID_SIZE.y / 2.0, cursor.z);
+        let entity = spawn_cuboid(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            transform,
+            NEW_CUBOID_SIZE,
+            color,
+        );
+        editor.selected = Some(entity);
+        editor.drag = None;
+        return;
+    }
+
+    if !mouse_button_input.pressed(MouseButton::Left) {
+        return;
+    }
+    let (Some(selected), Some(drag)) = (editor.selected, &editor.drag) else {
+        return;
+    };
+    let Ok((_, mut transform, cuboid)) = cuboid_query.get_mut(selected) else {
+        return;
+    };
+
+    match drag {
+        EditorDrag::Move { offset } => {
+            let translation = Vec3::new(
+                cursor.x + offset.x,
+                transform.translation.y,
+                cursor.z + offset.z,
+            );
+            if transform.translation != translation {
+                transform.translation = translation;
+            }
+        }
+        EditorDrag::Rotate => {
+            let direction = cursor - transform.translation;
+            if direction.x.abs() + direction.z.abs() < f32::EPSILON {
+                return;
+            }
+            // Turn the cuboid so that its local x axis points at the cursor
+            let rotation = Quat::from_rotation_y((-direction.z).atan2(direction.x));
+            if transform.rotation != rotation {
+                transform.rotation = rotation;
+            }
+        }
+        EditorDrag::Scale => {
+            let local = to_local(&transform, cursor);
+            let scale = Vec3::new(
+                (2.0 * local.x.abs() / cuboid.size.x).max(MIN_SCALE),
+                transform.scale.y,
+                (2.0 * local.z.abs() / cuboid.size.z).max(MIN_SCALE),
+            );
+            if transform.scale != scale {
+                transform.scale = scale;
+            }
+        }
+    }
+}
+
+pub fn draw_obstacle_editor(
+    editor: Res<ObstacleEditor>,
+    cuboid_query: Query<(&Transform, &CuboidObstacle)>,
+    mut gizmos: Gizmos,
+) {
+    if !editor.active {
+        return;
+    }
+    let Some((transform, cuboid)) = editor
+        .selected
+        .and_then(|entity| cuboid_query.get(entity).ok())
+    else {
+        return;
+    };
+
+    gizmos.cuboid(
+        transform.with_scale(transform.scale * cuboid.size * 1.05),
+        Color::srgb(1.0, 1.0, 1.0),
+    );
+
+    let lift = Vec3::Y * 0.05;
+    let rotate = rotate_handle(transform, cuboid);
+    gizmos.line(
+        transform.translation,
+        rotate + lift,
+        Color::srgb(1.0, 0.9, 0.0),
+    );
+    gizmos.circle(
+        rotate + lift,
+        Dir3::Y,
+        HANDLE_RADIUS,
+        Color::srgb(1.0, 0.9, 0.0),
+    );
+    gizmos.circle(
+        scale_handle(transform, cuboid) + lift,
+        Dir3::Y,
+        HANDLE_RADIUS,
+        Color::srgb(0.0, 0.9, 1.0),
+    );
+}
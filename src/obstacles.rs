@@ -1,8 +1,157 @@
-use crate::utils::Polygon;
+use crate::path_validation::ObstaclesChanged;
+use crate::pathfinding::NavMesh;
+use crate::utils::{convex_hull, point_in_polygon, weld_vertices, Capsule, Join, Point, Polygon};
+use crate::PathfindingConfig;
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
-use rand::Rng;
+use bevy::render::mesh::{Indices, PrimitiveTopology, VertexAttributeValues};
+use bevy::render::render_asset::RenderAssetUsages;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
 
-#[derive(Debug, Clone, Resource)]
+/// How far obstacle footprints are inflated to keep agents clear of them.
+pub const AGENT_BUFFER: f32 = 0.5;
+/// Corners of inflated obstacles closer than this are welded into one.
+pub const WELD_DISTANCE: f32 = 0.01;
+
+/// A rendered cuboid obstacle. `size` is the size of its mesh; the transform's scale
+/// applies on top, for rendering and for the footprint alike.
+#[derive(Component)]
+pub struct CuboidObstacle {
+    pub size: Vec3,
+}
+
+/// An obstacle with a free-form footprint, extruded to `height`. The footprint is the
+/// outline as drawn, wound like the cuboid polygons and not yet inflated.
+#[derive(Component)]
+pub struct PolygonObstacle {
+    pub footprint: Polygon,
+    pub height: f32,
+}
+
+/// The outline of the area agents may walk in, before it is shrunk by the agent buffer.
+/// Obstacles inside it are its holes. Without one the ground is open in every
+/// direction.
+#[derive(Component)]
+pub struct WalkableArea {
+    pub boundary: Polygon,
+}
+
+/// Makes an entity an obstacle shaped like its meshes, and those of its descendants
+/// such as a glTF scene: they are projected onto the ground and their convex hull
+/// becomes the footprint. The footprint follows the entity's `GlobalTransform`.
+#[derive(Component, Default)]
+pub struct NavObstacle;
+
+/// The footprint derived for a `NavObstacle`, before inflation.
+#[derive(Component)]
+pub struct NavObstacleFootprint {
+    pub footprint: Polygon,
+}
+
+/// A round or thin obstacle, placed on the ground by its entity's translation and
+/// rotation; the scale is ignored. Capsules and walls run along the local X axis.
+#[derive(Component, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum PrimitiveObstacle {
+    /// A tree or a pillar.
+    Circle { radius: f32, height: f32 },
+    Capsule {
+        half_length: f32,
+        radius: f32,
+        height: f32,
+    },
+    /// A fence or a thin wall. It may have no thickness at all; agents still keep the
+    /// agent buffer away from it.
+    Wall {
+        half_length: f32,
+        thickness: f32,
+        height: f32,
+    },
+}
+
+/// Thinner walls are drawn with this thickness so that they stay visible.
+const MIN_WALL_THICKNESS: f32 = 0.05;
+/// Corners per full turn of the rendered outline of circles and capsules.
+const ROUND_MESH_SEGMENTS: usize = 32;
+
+impl PrimitiveObstacle {
+    pub fn height(&self) -> f32 {
+        match *self {
+            PrimitiveObstacle::Circle { height, .. }
+            | PrimitiveObstacle::Capsule { height, .. }
+            | PrimitiveObstacle::Wall { height, .. } => height,
+        }
+    }
+
+    fn half_length(&self) -> f32 {
+        match *self {
+            PrimitiveObstacle::Circle { .. } => 0.0,
+            PrimitiveObstacle::Capsule { half_length, .. }
+            | PrimitiveObstacle::Wall { half_length, .. } => half_length,
+        }
+    }
+
+    /// The ends of the obstacle's centre line in world space.
+    pub fn segment(&self, transform: &Transform) -> (Vec3, Vec3) {
+        let axis = transform.rotation * Vec3::X * self.half_length();
+        (transform.translation - axis, transform.translation + axis)
+    }
+
+    /// Adds the obstacle, grown by the agent buffer, to the obstacle polygons.
+    pub fn add_to(
+        &self,
+        transform: &Transform,
+        polygons: &mut ObstaclePolygons,
+        agent_buffer: f32,
+    ) {
+        let (a, b) = self.segment(transform);
+        match *self {
+            PrimitiveObstacle::Circle { radius, .. } => {
+                polygons.add_circle(a, radius, agent_buffer);
+            }
+            PrimitiveObstacle::Capsule { radius, .. } => {
+                polygons.add_capsule(a, b, radius, agent_buffer);
+            }
+            PrimitiveObstacle::Wall { thickness, .. } => {
+                polygons.add_wall(a, b, thickness, agent_buffer);
+            }
+        }
+    }
+
+    /// The footprint the mesh is extruded from, around the origin of the entity.
+    fn mesh_footprint(&self) -> Polygon {
+        let half_length = self.half_length();
+        let capsule = |radius: f32| Capsule {
+            a: Point::from(Vec3::NEG_X * half_length),
+            b: Point::from(Vec3::X * half_length),
+            radius,
+        };
+        match *self {
+            PrimitiveObstacle::Circle { radius, .. }
+            | PrimitiveObstacle::Capsule { radius, .. } => {
+                capsule(radius).outline(ROUND_MESH_SEGMENTS, 0.0)
+            }
+            PrimitiveObstacle::Wall { thickness, .. } => {
+                let half_thickness = thickness.max(MIN_WALL_THICKNESS) / 2.0;
+                let mut footprint = Polygon::new();
+                footprint.add_vertex(-half_length, 0.0, -half_thickness);
+                footprint.add_vertex(-half_length, 0.0, half_thickness);
+                footprint.add_vertex(half_length, 0.0, half_thickness);
+                footprint.add_vertex(half_length, 0.0, -half_thickness);
+                footprint
+            }
+        }
+    }
+}
+
+/// The small markers drawn on every nav mesh vertex.
+#[derive(Component)]
+pub struct NavVertexMarker;
+
+#[derive(Debug, Clone, Resource, Default)]
 pub struct ObstaclePolygons {
     pub polygons: Vec<Polygon>,
 }
@@ -17,74 +166,297 @@ impl ObstaclePolygons {
     pub fn add_polygon(&mut self, polygon: Polygon) {
         self.polygons.push(polygon);
     }
+
+    /// A circle obstacle such as a tree or a pillar, grown by the agent radius. Its
+    /// outline corners, which become nav vertices, are the tangent points of paths
+    /// around it.
+    pub fn add_circle(&mut self, center: Vec3, radius: f32, agent_radius: f32) {
+        self.add_capsule(center, center, radius, agent_radius);
+    }
+
+    /// A capsule around the segment `a`-`b`, grown by the agent radius.
+    pub fn add_capsule(&mut self, a: Vec3, b: Vec3, radius: f32, agent_radius: f32) {
+        self.add_polygon(Polygon::from_capsule(Capsule {
+            a: Point::from(a),
+            b: Point::from(b),
+            radius: radius + agent_radius,
+        }));
+    }
+
+    /// A wall along the segment `a`-`b`, which may have no thickness. Grown by the
+    /// agent radius, it becomes a capsule.
+    pub fn add_wall(&mut self, a: Vec3, b: Vec3, thickness: f32, agent_radius: f32) {
+        self.add_capsule(a, b, thickness / 2.0, agent_radius);
+    }
+
+    /// Merges near-coincident corners of adjacent obstacles; see `weld_vertices`.
+    pub fn weld_vertices(&mut self, distance: f32) {
+        let dropped = weld_vertices(&mut self.polygons, distance);
+        if dropped > 0 {
+            println!("Dropped {dropped} obstacles that welding collapsed.");
+        }
+    }
+
+    /// Returns true when the point lies inside any obstacle, or outside the walkable
+    /// area.
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        let point = Point::from(point);
+        self.polygons
+            .iter()
+            .any(|polygon| point_in_polygon(&point, polygon) != polygon.is_walkable_boundary())
+    }
+}
+
+/// A circle on the ground that generation keeps free, such as the player spawn.
+#[derive(Debug, Clone)]
+pub struct KeepClearZone {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+/// The kind of layout world generation produces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WorldGenMode {
+    /// Randomly scattered, rotated cuboids.
+    #[default]
+    Scatter,
+    /// A perfect maze of narrow corridors.
+    Maze,
+    /// Rooms split by walls, connected through doorways.
+    Rooms,
+    /// City blocks with streets and alleys.
+    City,
+}
+
+impl WorldGenMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "scatter" => Some(WorldGenMode::Scatter),
+            "maze" => Some(WorldGenMode::Maze),
+            "rooms" => Some(WorldGenMode::Rooms),
+            "city" => Some(WorldGenMode::City),
+            _ => None,
+        }
+    }
+}
+
+/// Settings for world generation. The same seed and settings always produce the same
+/// layout.
+#[derive(Resource, Debug, Clone)]
+pub struct WorldGenConfig {
+    pub seed: u64,
+    pub mode: WorldGenMode,
+    /// How many cuboids to scatter; the other modes fill the bounds instead.
+    pub count: usize,
+    /// Obstacles stay within this distance of the origin along x and z.
+    pub bounds: f32,
+    /// The range scattered cuboids are scaled within.
+    pub scale_min: f32,
+    pub scale_max: f32,
+    /// The gap left between the inflated footprints of neighbouring obstacles.
+    pub min_spacing: f32,
+    pub keep_clear_zones: Vec<KeepClearZone>,
+    /// How far footprints are inflated and how their corners are joined. Keep them in
+    /// step with the `PathfindingConfig`, so that the spacing holds on the nav mesh.
+    pub agent_buffer: f32,
+    pub corner_join: Join,
 }
 
-pub fn generate_cuboids(obstacle_polygons: &mut ObstaclePolygons) -> Vec<(Transform, Vec3)> {
-    let mut rng = rand::thread_rng();
+impl WorldGenConfig {
+    pub fn with_seed(seed: u64) -> Self {
+        WorldGenConfig {
+            seed,
+            mode: WorldGenMode::default(),
+            count: 120,
+            bounds: 60.0,
+            scale_min: 0.75,
+            scale_max: 2.25,
+            min_spacing: 0.5,
+            keep_clear_zones: vec![KeepClearZone {
+                center: Vec3::ZERO,
+                radius: 3.0,
+            }],
+            agent_buffer: AGENT_BUFFER,
+            corner_join: Join::default(),
+        }
+    }
+
+    pub fn rng(&self) -> StdRng {
+        StdRng::seed_from_u64(self.seed)
+    }
+
+    /// A generator for what is added after the layout, such as obstacle colours and
+    /// wanderers, so that those follow from the seed too without shifting the layout.
+    pub fn detail_rng(&self) -> StdRng {
+        StdRng::seed_from_u64(self.seed ^ DETAIL_SEED_SALT)
+    }
+
+    /// Checks the settings that would otherwise make generation panic on an empty
+    /// range.
+    pub fn validate(&self) -> Result<(), WorldGenError> {
+        if !(self.bounds.is_finite() && self.bounds > 0.0) {
+            return Err(WorldGenError::Bounds(self.bounds));
+        }
+        if !(self.scale_min.is_finite()
+            && self.scale_max.is_finite()
+            && self.scale_min > 0.0
+            && self.scale_min < self.scale_max)
+        {
+            return Err(WorldGenError::ScaleRange {
+                min: self.scale_min,
+                max: self.scale_max,
+            });
+        }
+        if !(self.min_spacing.is_finite() && self.min_spacing >= 0.0) {
+            return Err(WorldGenError::Spacing(self.min_spacing));
+        }
+        if !(self.agent_buffer.is_finite() && self.agent_buffer >= 0.0) {
+            return Err(WorldGenError::AgentBuffer(self.agent_buffer));
+        }
+        if let Some(zone) = self
+            .keep_clear_zones
+            .iter()
+            .find(|zone| !(zone.radius.is_finite() && zone.radius >= 0.0))
+        {
+            return Err(WorldGenError::ZoneRadius(zone.radius));
+        }
+        Ok(())
+    }
+}
+
+/// Mixed into the seed for everything generated after the layout.
+const DETAIL_SEED_SALT: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// A `WorldGenConfig` setting that generation cannot work with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WorldGenError {
+    /// `bounds` is not a positive number.
+    Bounds(f32),
+    /// `scale_min..scale_max` is empty or not positive.
+    ScaleRange { min: f32, max: f32 },
+    /// `min_spacing` is negative or not a number.
+    Spacing(f32),
+    /// A keep-clear zone radius is negative or not a number.
+    ZoneRadius(f32),
+    /// `agent_buffer` is negative or not a number.
+    AgentBuffer(f32),
+}
+
+impl fmt::Display for WorldGenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WorldGenError::Bounds(bounds) => write!(f, "bounds {bounds} is not positive"),
+            WorldGenError::ScaleRange { min, max } => {
+                write!(f, "scale range {min}..{max} is empty or not positive")
+            }
+            WorldGenError::Spacing(spacing) => write!(f, "spacing {spacing} is negative"),
+            WorldGenError::ZoneRadius(radius) => {
+                write!(f, "keep-clear radius {radius} is negative")
+            }
+            WorldGenError::AgentBuffer(buffer) => {
+                write!(f, "agent buffer {buffer} is negative")
+            }
+        }
+    }
+}
+
+impl std::error::Error for WorldGenError {}
+
+/// How many positions are tried for an obstacle before it is left out.
+const PLACEMENT_ATTEMPTS: usize = 30;
+
+/// Radius of the circle around `center` that holds the whole polygon.
+fn bounding_radius(center: Vec3, polygon: &Polygon) -> f32 {
+    polygon
+        .vertices
+        .iter()
+        .map(|vertex| Vec2::new(vertex.x - center.x, vertex.z - center.z).length())
+        .fold(0.0, f32::max)
+}
+
+pub fn generate_cuboids(
+    config: &WorldGenConfig,
+    obstacle_polygons: &mut ObstaclePolygons,
+) -> Vec<(Transform, Vec3)> {
+    let mut rng = config.rng();
     let mut transforms_and_scales = Vec::new();
+    let mut placed: Vec<(Vec3, f32)> = Vec::new();
+
+    for _ in 0..config.count {
+        for _ in 0..PLACEMENT_ATTEMPTS {
+            let scale_x = rng.gen_range(config.scale_min..config.scale_max);
+            let scale_y = 1.0;
+            let scale_z = rng.gen_range(config.scale_min..config.scale_max);
 
-    for _ in 0..120 {
-        let scale_x = rng.gen_range(0.75..2.25);
-        let scale_y = 1.0;
-        let scale_z = rng.gen_range(0.75..2.25);
+            let x = rng.gen_range(-config.bounds..config.bounds);
+            let y = 0.5;
+            let z = rng.gen_range(-config.bounds..config.bounds);
 
-        let x = rng.gen_range(-60.0..60.0);
-        let y = 0.5;
-        let z = rng.gen_range(-60.0..60.0);
+            let rotation_x = 0.0;
+            let rotation_y = rng.gen_range(0.0..std::f32::consts::PI * 2.0);
+            let rotation_z = 0.0;
 
-        let rotation_x = 0.0;
-        let rotation_y = rng.gen_range(0.0..std::f32::consts::PI * 2.0);
-        let rotation_z = 0.0;
+            let transform = Transform::from_xyz(x, y, z)
+                .with_scale(Vec3::new(scale_x, scale_y, scale_z))
+                .with_rotation(Quat::from_euler(
+                    EulerRot::XYZ,
+                    rotation_x,
+                    rotation_y,
+                    rotation_z,
+                ));
 
-        let transform = Transform::from_xyz(x, y, z)
-            .with_scale(Vec3::new(scale_x, scale_y, scale_z))
-            .with_rotation(Quat::from_euler(
-                EulerRot::XYZ,
-                rotation_x,
-                rotation_y,
-                rotation_z,
-            ));
+            let polygon = generate_cuboid_polygon(
+                transform,
+                scale_x,
+                scale_y,
+                scale_z,
+                config.agent_buffer,
+                config.corner_join,
+            );
+            let center = Vec3::new(x, 0.0, z);
+            let radius = bounding_radius(center, &polygon);
 
-        transforms_and_scales.push((transform, Vec3::new(scale_x, scale_y, scale_z)));
+            // Bounding circles are conservative, so tightly packed layouts leave gaps
+            let in_zone = config
+                .keep_clear_zones
+                .iter()
+                .any(|zone| zone.center.xz().distance(center.xz()) < zone.radius + radius);
+            let too_close = placed.iter().any(|(other, other_radius)| {
+                other.xz().distance(center.xz()) < radius + other_radius + config.min_spacing
+            });
+            if in_zone || too_close {
+                continue;
+            }
 
-        let polygon = generate_cuboid_polygon(transform, scale_x, scale_y, scale_z);
-        obstacle_polygons.add_polygon(polygon);
+            placed.push((center, radius));
+            transforms_and_scales.push((transform, Vec3::new(scale_x, scale_y, scale_z)));
+            obstacle_polygons.add_polygon(polygon);
+            break;
+        }
     }
 
     transforms_and_scales
 }
 
+/// The footprint of a cuboid, grown by `buffer` with `join` at its corners. The buffer
+/// is added after the transform, so that a scaled cuboid does not stretch its
+/// clearance.
 pub fn generate_cuboid_polygon(
     transform: Transform,
     scale_x: f32,
     scale_y: f32,
     scale_z: f32,
+    buffer: f32,
+    join: Join,
 ) -> Polygon {
     let mut polygon = Polygon::new();
-    let buffer = 0.5;
 
     // Vertices are ordered counterclockwise when viewed from above
     let vertices = vec![
-        Vec3::new(
-            -scale_x / 2.0 - buffer,
-            -scale_y / 2.0,
-            -scale_z / 2.0 - buffer,
-        ), // Bottom-left corner
-        Vec3::new(
-            -scale_x / 2.0 - buffer,
-            -scale_y / 2.0,
-            scale_z / 2.0 + buffer,
-        ), // Top-left corner
-        Vec3::new(
-            scale_x / 2.0 + buffer,
-            -scale_y / 2.0,
-            scale_z / 2.0 + buffer,
-        ), // Top-right corner
-        Vec3::new(
-            scale_x / 2.0 + buffer,
-            -scale_y / 2.0,
-            -scale_z / 2.0 - buffer,
-        ), // Bottom-right corner
+        Vec3::new(-scale_x / 2.0, -scale_y / 2.0, -scale_z / 2.0), // Bottom-left corner
+        Vec3::new(-scale_x / 2.0, -scale_y / 2.0, scale_z / 2.0),  // Top-left corner
+        Vec3::new(scale_x / 2.0, -scale_y / 2.0, scale_z / 2.0),   // Top-right corner
+        Vec3::new(scale_x / 2.0, -scale_y / 2.0, -scale_z / 2.0),  // Bottom-right corner
     ];
 
     for vertex in vertices {
@@ -96,29 +468,451 @@ pub fn generate_cuboid_polygon(
         );
     }
 
-    polygon
+    polygon.offset(buffer, join)
 }
 
+/// Spawns the generated cuboids, coloured at random by `rng`.
 pub fn render_cuboids(
     commands: &mut Commands,
     meshes: &mut ResMut<Assets<Mesh>>,
     materials: &mut ResMut<Assets<StandardMaterial>>,
     transforms_and_scales: Vec<(Transform, Vec3)>,
+    rng: &mut impl Rng,
 ) {
-    let mut rng = rand::thread_rng();
-
     for (transform, scale) in transforms_and_scales {
         // Generate random RGB values between 0.0 and 1.0
         let random_color = Color::srgb(rng.gen::<f32>(), rng.gen::<f32>(), rng.gen::<f32>());
 
-        commands.spawn(PbrBundle {
-            mesh: meshes.add(Cuboid::new(scale.x, scale.y, scale.z)),
-            material: materials.add(StandardMaterial {
-                base_color: random_color,
+        spawn_cuboid(commands, meshes, materials, transform, scale, random_color);
+    }
+}
+
+pub fn spawn_cuboid(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    transform: Transform,
+    size: Vec3,
+    color: Color,
+) -> Entity {
+    commands
+        .spawn((
+            PbrBundle {
+                mesh: meshes.add(Cuboid::new(size.x, size.y, size.z)),
+                material: materials.add(StandardMaterial {
+                    base_color: color,
+                    ..default()
+                }),
+                transform,
+                ..default()
+            },
+            CuboidObstacle { size },
+        ))
+        .id()
+}
+
+pub fn spawn_polygon_obstacle(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    footprint: Polygon,
+    height: f32,
+    color: Color,
+) -> Entity {
+    commands
+        .spawn((
+            PbrBundle {
+                mesh: meshes.add(extrude_polygon(&footprint, height)),
+                material: materials.add(StandardMaterial {
+                    base_color: color,
+                    ..default()
+                }),
+                ..default()
+            },
+            PolygonObstacle { footprint, height },
+        ))
+        .id()
+}
+
+/// Spawns a round or thin obstacle with a mesh extruded from its footprint.
+pub fn spawn_primitive_obstacle(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    transform: Transform,
+    primitive: PrimitiveObstacle,
+    color: Color,
+) -> Entity {
+    commands
+        .spawn((
+            PbrBundle {
+                mesh: meshes.add(extrude_polygon(
+                    &primitive.mesh_footprint(),
+                    primitive.height(),
+                )),
+                material: materials.add(StandardMaterial {
+                    base_color: color,
+                    ..default()
+                }),
+                transform,
                 ..default()
-            }),
-            transform,
-            ..default()
+            },
+            primitive,
+        ))
+        .id()
+}
+
+/// Builds a prism from a footprint: the top face and the walls, with flat normals. The
+/// bottom is left out as it rests on the ground.
+fn extrude_polygon(footprint: &Polygon, height: f32) -> Mesh {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut normals: Vec<[f32; 3]> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
+
+    // Front faces are counterclockwise seen from outside, which from above is the
+    // winding of the footprint
+    for vertex in &footprint.vertices {
+        positions.push([vertex.x, vertex.y + height, vertex.z]);
+        normals.push([0.0, 1.0, 0.0]);
+    }
+    for triangle in footprint.triangulate() {
+        indices.extend(triangle.iter().map(|&i| i as u32));
+    }
+
+    let clockwise = footprint.signed_area() < 0.0;
+    let n = footprint.vertices.len();
+    for i in 0..n {
+        let a = &footprint.vertices[i];
+        let b = &footprint.vertices[(i + 1) % n];
+        let edge = Vec2::new(b.x - a.x, b.z - a.z).normalize_or_zero();
+        let normal = if clockwise {
+            [-edge.y, 0.0, edge.x]
+        } else {
+            [edge.y, 0.0, -edge.x]
+        };
+
+        let base = positions.len() as u32;
+        positions.extend([
+            [a.x, a.y, a.z],
+            [b.x, b.y, b.z],
+            [b.x, b.y + height, b.z],
+            [a.x, a.y + height, a.z],
+        ]);
+        normals.extend([normal; 4]);
+        if clockwise {
+            indices.extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+        } else {
+            indices.extend([base, base + 2, base + 1, base, base + 3, base + 2]);
+        }
+    }
+
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+    .with_inserted_indices(Indices::U32(indices))
+}
+
+/// Keeps the footprint of every `NavObstacle` up to date. It is derived again when a
+/// mesh of the obstacle moves, is swapped, or finishes loading or changes as an asset;
+/// a scene that has not spawned its meshes yet gets no footprint until it has. The
+/// footprint is only replaced when its outline changed.
+#[allow(clippy::type_complexity)]
+pub fn update_nav_obstacle_footprints(
+    mut commands: Commands,
+    meshes: Res<Assets<Mesh>>,
+    mut mesh_events: EventReader<AssetEvent<Mesh>>,
+    obstacle_query: Query<(Entity, Option<&NavObstacleFootprint>), With<NavObstacle>>,
+    children_query: Query<&Children>,
+    part_query: Query<(Ref<GlobalTransform>, Option<Ref<Handle<Mesh>>>)>,
+) {
+    let changed_meshes: HashSet<AssetId<Mesh>> = mesh_events
+        .read()
+        .filter_map(|event| match *event {
+            AssetEvent::Added { id }
+            | AssetEvent::Modified { id }
+            | AssetEvent::LoadedWithDependencies { id }
+            | AssetEvent::Removed { id } => Some(id),
+            AssetEvent::Unused { .. } => None,
+        })
+        .collect();
+
+    for (entity, current) in &obstacle_query {
+        let parts: Vec<Entity> = std::iter::once(entity)
+            .chain(children_query.iter_descendants(entity))
+            .collect();
+        let changed = parts.iter().any(|&part| {
+            part_query.get(part).is_ok_and(|(transform, mesh)| {
+                transform.is_changed()
+                    || mesh.is_some_and(|mesh| {
+                        mesh.is_changed() || changed_meshes.contains(&mesh.id())
+                    })
+            })
         });
+        if current.is_some() && !changed {
+            continue;
+        }
+
+        let mut points = Vec::new();
+        for (transform, mesh) in parts.iter().filter_map(|&part| part_query.get(part).ok()) {
+            let Some(mesh) = mesh.and_then(|handle| meshes.get(&*handle)) else {
+                continue;
+            };
+            if let Some(VertexAttributeValues::Float32x3(positions)) =
+                mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+            {
+                points.extend(positions.iter().map(|position| {
+                    let mut point =
+                        Point::from(transform.transform_point(Vec3::from_array(*position)));
+                    point.y = 0.0;
+                    point
+                }));
+            }
+        }
+
+        let footprint = convex_hull(&points);
+        if footprint.vertices.len() >= 3 {
+            if current.is_some_and(|current| current.footprint.vertices == footprint.vertices) {
+                continue;
+            }
+            commands
+                .entity(entity)
+                .insert(NavObstacleFootprint { footprint });
+        } else if current.is_some() {
+            commands.entity(entity).remove::<NavObstacleFootprint>();
+        }
+    }
+}
+
+/// Every kind of obstacle component that can be removed, read together.
+#[derive(SystemParam)]
+pub struct RemovedObstacles<'w, 's> {
+    cuboids: RemovedComponents<'w, 's, CuboidObstacle>,
+    footprints: RemovedComponents<'w, 's, PolygonObstacle>,
+    nav_obstacles: RemovedComponents<'w, 's, NavObstacleFootprint>,
+    primitives: RemovedComponents<'w, 's, PrimitiveObstacle>,
+    areas: RemovedComponents<'w, 's, WalkableArea>,
+}
+
+impl RemovedObstacles<'_, '_> {
+    /// Whether any obstacle was removed since the last call.
+    fn any(&mut self) -> bool {
+        self.cuboids.read().count()
+            + self.footprints.read().count()
+            + self.nav_obstacles.read().count()
+            + self.primitives.read().count()
+            + self.areas.read().count()
+            > 0
+    }
+}
+
+/// Regenerates `ObstaclePolygons` and the `NavMesh` from the obstacle entities whenever
+/// one of them is added, moved, resized or removed.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn rebuild_obstacles(
+    changed_cuboids: Query<
+        (),
+        (
+            With<CuboidObstacle>,
+            Or<(Changed<Transform>, Changed<CuboidObstacle>)>,
+        ),
+    >,
+    changed_primitives: Query<
+        (),
+        (
+            With<PrimitiveObstacle>,
+            Or<(Changed<Transform>, Changed<PrimitiveObstacle>)>,
+        ),
+    >,
+    changed_outlines: Query<
+        (),
+        Or<(
+            Changed<PolygonObstacle>,
+            Changed<NavObstacleFootprint>,
+            Changed<WalkableArea>,
+        )>,
+    >,
+    mut removed: RemovedObstacles,
+    cuboid_query: Query<(&Transform, &CuboidObstacle)>,
+    footprint_query: Query<&PolygonObstacle>,
+    nav_obstacle_query: Query<&NavObstacleFootprint>,
+    primitive_query: Query<(&Transform, &PrimitiveObstacle)>,
+    area_query: Query<&WalkableArea>,
+    config: Res<PathfindingConfig>,
+    mut obstacle_polygons: ResMut<ObstaclePolygons>,
+    mut nav_mesh: ResMut<NavMesh>,
+    mut obstacles_changed: EventWriter<ObstaclesChanged>,
+) {
+    let removed_any = removed.any();
+    if changed_cuboids.is_empty()
+        && changed_primitives.is_empty()
+        && changed_outlines.is_empty()
+        && !removed_any
+        && !config.is_changed()
+    {
+        return;
+    }
+
+    // The winding tells obstacles (clockwise) from walkable boundaries apart
+    let mut polygons = ObstaclePolygons::new();
+    for (transform, cuboid) in &cuboid_query {
+        let mut polygon = generate_cuboid_polygon(
+            *transform,
+            cuboid.size.x,
+            cuboid.size.y,
+            cuboid.size.z,
+            config.agent_buffer,
+            config.corner_join,
+        );
+        polygon.set_clockwise(true);
+        polygons.add_polygon(polygon);
+    }
+    // Footprints from drawings, levels and meshes are checked, and repaired if need be
+    let footprints = footprint_query
+        .iter()
+        .map(|obstacle| &obstacle.footprint)
+        .chain(
+            nav_obstacle_query
+                .iter()
+                .map(|obstacle| &obstacle.footprint),
+        );
+    for footprint in footprints {
+        match footprint.repaired() {
+            Ok(footprint) => {
+                polygons.add_polygon(footprint.offset(config.agent_buffer, config.corner_join))
+            }
+            Err(error) => println!("Skipping an obstacle footprint: {error}."),
+        }
+    }
+    for (transform, primitive) in &primitive_query {
+        primitive.add_to(transform, &mut polygons, config.agent_buffer);
+    }
+    for area in &area_query {
+        match area.boundary.repaired() {
+            Ok(boundary) => {
+                let mut polygon = boundary.offset(-config.agent_buffer, config.corner_join);
+                polygon.set_clockwise(false);
+                polygons.add_polygon(polygon);
+            }
+            Err(error) => println!("Skipping the walkable area: {error}."),
+        }
+    }
+
+    polygons.weld_vertices(WELD_DISTANCE);
+
+    *nav_mesh = NavMesh::from_polygons(&polygons).with_planner(config.planner);
+    *obstacle_polygons = polygons;
+    obstacles_changed.send(ObstaclesChanged);
+}
+
+/// Outlines the walkable area, if there is one.
+pub fn draw_walkable_area(area_query: Query<&WalkableArea>, mut gizmos: Gizmos) {
+    let lift = Vec3::Y * 0.05;
+    for area in &area_query {
+        let corners: Vec<Vec3> = area.boundary.vertices.iter().map(Vec3::from).collect();
+        if let Some(first) = corners.first() {
+            gizmos.linestrip(
+                corners
+                    .iter()
+                    .chain(std::iter::once(first))
+                    .map(|corner| *corner + lift),
+                Color::srgb(0.2, 0.9, 0.4),
+            );
+        }
+    }
+}
+
+/// Respawns the vertex markers whenever the nav mesh is rebuilt.
+pub fn refresh_nav_vertex_markers(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    nav_mesh: Res<NavMesh>,
+    marker_query: Query<Entity, With<NavVertexMarker>>,
+) {
+    if !nav_mesh.is_changed() {
+        return;
+    }
+
+    for marker in &marker_query {
+        commands.entity(marker).despawn();
+    }
+
+    let mesh = meshes.add(Cuboid::new(0.1, 0.1, 0.1));
+    let material = materials.add(StandardMaterial {
+        base_color: Color::WHITE,
+        ..default()
+    });
+    for vertex in &nav_mesh.vertices {
+        commands.spawn((
+            PbrBundle {
+                mesh: mesh.clone(),
+                material: material.clone(),
+                transform: Transform::from_xyz(vertex.x, vertex.y, vertex.z),
+                ..default()
+            },
+            NavVertexMarker,
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nav_obstacle_footprint_follows_rotation_and_scale() {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            TransformPlugin,
+            HierarchyPlugin,
+            AssetPlugin {
+                watch_for_changes_override: Some(false),
+                ..default()
+            },
+        ))
+        .init_asset::<Mesh>()
+        .add_systems(Update, update_nav_obstacle_footprints);
+
+        let mesh = app
+            .world_mut()
+            .resource_mut::<Assets<Mesh>>()
+            .add(Cuboid::new(1.0, 1.0, 1.0));
+        // A 2 by 1 footprint turned a quarter turn about y, on a child mesh like a scene's
+        let obstacle = app
+            .world_mut()
+            .spawn((
+                SpatialBundle::from_transform(
+                    Transform::from_xyz(5.0, 0.0, 3.0)
+                        .with_rotation(Quat::from_rotation_y(std::f32::consts::FRAC_PI_2))
+                        .with_scale(Vec3::new(2.0, 1.0, 1.0)),
+                ),
+                NavObstacle,
+            ))
+            .with_children(|parent| {
+                parent.spawn((SpatialBundle::default(), mesh));
+            })
+            .id();
+
+        // Transforms propagate after the first update, the footprint follows on the next
+        app.update();
+        app.update();
+
+        let footprint = &app
+            .world()
+            .get::<NavObstacleFootprint>(obstacle)
+            .expect("the footprint is derived")
+            .footprint;
+        assert_eq!(footprint.vertices.len(), 4);
+        assert!((footprint.area() - 2.0).abs() < 1e-4);
+        for vertex in &footprint.vertices {
+            assert_eq!(vertex.y, 0.0);
+            // The long side now runs along z
+            assert!(((vertex.x - 5.0).abs() - 0.5).abs() < 1e-4, "{vertex:?}");
+            assert!(((vertex.z - 3.0).abs() - 1.0).abs() < 1e-4, "{vertex:?}");
+        }
     }
 }
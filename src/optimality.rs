@@ -0,0 +1,413 @@
+use crate::obstacles::ObstaclePolygons;
+use crate::pathfinding::{
+    find_path, path_length, visibility_dijkstra, NavMesh, PathQueryStats, Planner,
+};
+use crate::utils::{
+    orientation, point_in_polygon, point_segment_distance, segment_distance, Point, Polygon,
+};
+use bevy::prelude::*;
+use rand::Rng;
+use std::cmp::Ordering;
+use std::fmt;
+
+/// How much longer than the shortest path a Theta* path may be before F4 flags it.
+pub const DEFAULT_TOLERANCE: f32 = 0.05;
+/// Queries F4 runs on the current map.
+const CHECK_QUERIES: usize = 100;
+/// Query endpoints are picked within this distance of the origin on both axes.
+const QUERY_RANGE: f32 = 50.0;
+/// Random points tried for each query endpoint.
+const POINT_ATTEMPTS: usize = 100;
+/// How far into an obstacle a segment's midpoint has to lie to count as crossing it, so
+/// that segments running along an edge do not.
+const CROSSING_DEPTH: f32 = 1e-3;
+
+/// One query answered by both Theta* and the exact planner.
+#[derive(Debug, Clone)]
+pub struct QueryComparison {
+    pub start: Vec3,
+    pub goal: Vec3,
+    /// Length of the Theta* path, `None` when it found none.
+    pub theta_star_length: Option<f32>,
+    /// Length of the shortest path, `None` when there is none.
+    pub exact_length: Option<f32>,
+    /// Whether the Theta* path keeps clear of every obstacle, by `path_crosses_obstacle`.
+    pub theta_star_clear: bool,
+}
+
+impl QueryComparison {
+    /// Theta* length over the shortest length, when both found a path.
+    pub fn ratio(&self) -> Option<f32> {
+        match (self.theta_star_length, self.exact_length) {
+            (Some(theta_star), Some(exact)) if exact > 0.0 => Some(theta_star / exact),
+            (Some(_), Some(_)) => Some(1.0),
+            _ => None,
+        }
+    }
+}
+
+/// What is wrong with a Theta* answer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OptimalityIssue {
+    /// Longer than the shortest path by more than the tolerance.
+    Suboptimal { ratio: f32 },
+    /// The path runs through an obstacle.
+    CrossesObstacle,
+    /// No path found although one exists.
+    MissedPath,
+}
+
+impl fmt::Display for OptimalityIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OptimalityIssue::Suboptimal { ratio } => {
+                write!(
+                    f,
+                    "{:.1}% longer than the shortest path",
+                    (ratio - 1.0) * 100.0
+                )
+            }
+            OptimalityIssue::CrossesObstacle => write!(f, "crosses an obstacle"),
+            OptimalityIssue::MissedPath => write!(f, "no path, but one exists"),
+        }
+    }
+}
+
+/// The outcome of `compare_planners`.
+#[derive(Debug, Clone)]
+pub struct OptimalityReport {
+    pub comparisons: Vec<QueryComparison>,
+    /// How much longer than the shortest path a Theta* path may be, as a fraction.
+    pub tolerance: f32,
+}
+
+impl OptimalityReport {
+    /// The queries Theta* got wrong, with what is wrong with each.
+    pub fn flagged(&self) -> Vec<(&QueryComparison, OptimalityIssue)> {
+        let mut flagged = Vec::new();
+        for comparison in &self.comparisons {
+            if comparison.theta_star_length.is_some() && !comparison.theta_star_clear {
+                flagged.push((comparison, OptimalityIssue::CrossesObstacle));
+            }
+            match comparison.ratio() {
+                Some(ratio) if ratio > 1.0 + self.tolerance => {
+                    flagged.push((comparison, OptimalityIssue::Suboptimal { ratio }));
+                }
+                None if comparison.exact_length.is_some() => {
+                    flagged.push((comparison, OptimalityIssue::MissedPath));
+                }
+                _ => {}
+            }
+        }
+        flagged
+    }
+
+    pub fn mean_ratio(&self) -> Option<f32> {
+        let ratios: Vec<f32> = self.comparisons.iter().filter_map(|c| c.ratio()).collect();
+        (!ratios.is_empty()).then(|| ratios.iter().sum::<f32>() / ratios.len() as f32)
+    }
+
+    pub fn worst_ratio(&self) -> Option<f32> {
+        self.comparisons
+            .iter()
+            .filter_map(|comparison| comparison.ratio())
+            .max_by(f32::total_cmp)
+    }
+}
+
+impl fmt::Display for OptimalityReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let flagged = self.flagged();
+        write!(
+            f,
+            "{} queries, Theta*/shortest length ratio mean {:.4}, worst {:.4}, {} flagged \
+             at {:.1}% tolerance",
+            self.comparisons.len(),
+            self.mean_ratio().unwrap_or(1.0),
+            self.worst_ratio().unwrap_or(1.0),
+            flagged.len(),
+            self.tolerance * 100.0
+        )?;
+        for (comparison, issue) in flagged {
+            write!(
+                f,
+                "\n  {:?} -> {:?}: {issue}",
+                comparison.start, comparison.goal
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs `find_path` with Theta*, as the game does, and Dijkstra over the visibility
+/// graph against all obstacles on every query, and compares the paths. Neither counts
+/// towards the pathfinding diagnostics.
+pub fn compare_planners(
+    nav_mesh: &NavMesh,
+    obstacle_polygons: &ObstaclePolygons,
+    queries: &[(Vec3, Vec3)],
+    tolerance: f32,
+) -> OptimalityReport {
+    let theta_star_mesh = nav_mesh.clone().with_planner(Planner::ThetaStar);
+    let mut query_stats = PathQueryStats::default();
+    let comparisons = queries
+        .iter()
+        .map(|&(start, goal)| {
+            // Direct paths leave out their start, searched ones repeat it
+            let theta_star_path: Vec<Point> = find_path(
+                &theta_star_mesh,
+                obstacle_polygons,
+                start,
+                goal,
+                &mut query_stats,
+            )
+            .map(|path| {
+                std::iter::once(start)
+                    .chain(path)
+                    .map(Point::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+            let (exact_path, _) = visibility_dijkstra(
+                nav_mesh,
+                Point::from(start),
+                Point::from(goal),
+                &obstacle_polygons.polygons,
+                None,
+            );
+            let length = |path: &[Point]| {
+                let path: Vec<Vec3> = path.iter().map(Vec3::from).collect();
+                (!path.is_empty()).then(|| path_length(&path))
+            };
+
+            QueryComparison {
+                start,
+                goal,
+                theta_star_length: length(&theta_star_path),
+                exact_length: length(&exact_path),
+                theta_star_clear: !path_crosses_obstacle(obstacle_polygons, &theta_star_path),
+            }
+        })
+        .collect();
+
+    OptimalityReport {
+        comparisons,
+        tolerance,
+    }
+}
+
+/// Whether any segment of the path passes through an obstacle or out of the walkable
+/// area. This does not share the line-of-sight test the planners search with: a
+/// segment fails when it properly crosses an edge, or when its midpoint lies inside an
+/// obstacle (outside a walkable boundary) by more than `CROSSING_DEPTH`. Round
+/// obstacles are checked against their exact shape.
+pub fn path_crosses_obstacle(obstacle_polygons: &ObstaclePolygons, path: &[Point]) -> bool {
+    path.windows(2).any(|segment| {
+        obstacle_polygons
+            .polygons
+            .iter()
+            .any(|polygon| segment_crosses(&segment[0], &segment[1], polygon))
+    })
+}
+
+fn segment_crosses(a: &Point, b: &Point, polygon: &Polygon) -> bool {
+    if let Some(capsule) = &polygon.capsule {
+        return segment_distance(a, b, &capsule.a, &capsule.b) < capsule.radius - CROSSING_DEPTH;
+    }
+
+    let n = polygon.vertices.len();
+    let opposite = |first: Ordering, second: Ordering| {
+        first != Ordering::Equal && second != Ordering::Equal && first != second
+    };
+    let properly_crosses = (0..n).any(|i| {
+        let (c, d) = (&polygon.vertices[i], &polygon.vertices[(i + 1) % n]);
+        opposite(orientation(a, b, c), orientation(a, b, d))
+            && opposite(orientation(c, d, a), orientation(c, d, b))
+    });
+    if properly_crosses {
+        return true;
+    }
+
+    let midpoint = Point {
+        x: (a.x + b.x) / 2.0,
+        y: a.y,
+        z: (a.z + b.z) / 2.0,
+    };
+    let depth = (0..n)
+        .map(|i| {
+            point_segment_distance(
+                &midpoint,
+                &polygon.vertices[i],
+                &polygon.vertices[(i + 1) % n],
+            )
+        })
+        .fold(f32::INFINITY, f32::min);
+    point_in_polygon(&midpoint, polygon) != polygon.is_walkable_boundary() && depth > CROSSING_DEPTH
+}
+
+/// Random queries between points outside every obstacle. Fewer come back when open
+/// points are too hard to find.
+pub fn random_queries(
+    rng: &mut impl Rng,
+    obstacle_polygons: &ObstaclePolygons,
+    count: usize,
+    range: f32,
+) -> Vec<(Vec3, Vec3)> {
+    let mut open_point = || {
+        (0..POINT_ATTEMPTS)
+            .map(|_| {
+                Vec3::new(
+                    rng.gen_range(-range..range),
+                    0.0,
+                    rng.gen_range(-range..range),
+                )
+            })
+            .find(|point| !obstacle_polygons.contains_point(*point))
+    };
+    (0..count)
+        .filter_map(|_| Some((open_point()?, open_point()?)))
+        .collect()
+}
+
+/// F4 compares Theta* with the shortest paths on random queries over the current map.
+pub fn run_optimality_check(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    nav_mesh: Res<NavMesh>,
+    obstacle_polygons: Res<ObstaclePolygons>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F4) {
+        return;
+    }
+
+    let queries = random_queries(
+        &mut rand::thread_rng(),
+        &obstacle_polygons,
+        CHECK_QUERIES,
+        QUERY_RANGE,
+    );
+    let report = compare_planners(&nav_mesh, &obstacle_polygons, &queries, DEFAULT_TOLERANCE);
+    println!("{report}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A polygon from (x, z) corners, wound as given.
+    fn polygon(corners: &[(f32, f32)]) -> Polygon {
+        let mut polygon = Polygon::new();
+        for &(x, z) in corners {
+            polygon.add_vertex(x, 0.0, z);
+        }
+        polygon
+    }
+
+    fn points(corners: &[(f32, f32)]) -> Vec<Point> {
+        polygon(corners).vertices
+    }
+
+    /// A 2 by 2 square around the origin. Going from (-3, 0) to (3, 0) the shortest path
+    /// runs over its two corners at z = 1, 2 + 2√5 long.
+    fn square_map() -> (NavMesh, ObstaclePolygons) {
+        let mut square = polygon(&[(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]);
+        square.set_clockwise(true);
+        let mut obstacle_polygons = ObstaclePolygons::new();
+        obstacle_polygons.add_polygon(square);
+        (
+            NavMesh::from_polygons(&obstacle_polygons),
+            obstacle_polygons,
+        )
+    }
+
+    #[test]
+    fn finds_the_known_shortest_path() {
+        let (nav_mesh, obstacle_polygons) = square_map();
+        let queries = [
+            (Vec3::new(-3.0, 0.0, 0.0), Vec3::new(3.0, 0.0, 0.0)),
+            (Vec3::new(-3.0, 0.0, 3.0), Vec3::new(3.0, 0.0, 3.0)),
+        ];
+        let report = compare_planners(&nav_mesh, &obstacle_polygons, &queries, 0.0);
+
+        let around = 2.0 + 2.0 * 5f32.sqrt();
+        let first = &report.comparisons[0];
+        assert!((first.exact_length.unwrap() - around).abs() < 1e-4);
+        assert!((first.theta_star_length.unwrap() - around).abs() < 1e-4);
+        assert!(first.theta_star_clear);
+        // The second query is answered directly by find_path's shortcut
+        let second = &report.comparisons[1];
+        assert!((second.theta_star_length.unwrap() - 6.0).abs() < 1e-4);
+        assert!(second.theta_star_clear);
+        assert!(report.flagged().is_empty());
+    }
+
+    #[test]
+    fn flags_paths_through_obstacles() {
+        let (_, obstacle_polygons) = square_map();
+        let through = points(&[(-3.0, 0.0), (3.0, 0.0)]);
+        assert!(path_crosses_obstacle(&obstacle_polygons, &through));
+        // Corner to corner across the inside, with no edge properly crossed
+        let diagonal = points(&[(-1.0, -1.0), (1.0, 1.0)]);
+        assert!(path_crosses_obstacle(&obstacle_polygons, &diagonal));
+
+        let around = points(&[(-3.0, 0.0), (-1.0, 1.0), (1.0, 1.0), (3.0, 0.0)]);
+        assert!(!path_crosses_obstacle(&obstacle_polygons, &around));
+    }
+
+    #[test]
+    fn flags_paths_leaving_the_walkable_area() {
+        let mut boundary = polygon(&[(-5.0, -5.0), (5.0, -5.0), (5.0, 5.0), (-5.0, 5.0)]);
+        boundary.set_clockwise(false);
+        let mut obstacle_polygons = ObstaclePolygons::new();
+        obstacle_polygons.add_polygon(boundary);
+
+        let inside = points(&[(-4.0, 0.0), (4.0, 0.0)]);
+        assert!(!path_crosses_obstacle(&obstacle_polygons, &inside));
+        let outside = points(&[(-4.0, 0.0), (6.0, 0.0)]);
+        assert!(path_crosses_obstacle(&obstacle_polygons, &outside));
+    }
+
+    #[test]
+    fn flags_round_obstacles_by_their_exact_shape() {
+        let mut obstacle_polygons = ObstaclePolygons::new();
+        obstacle_polygons.add_circle(Vec3::ZERO, 1.0, 0.0);
+
+        let through = points(&[(-3.0, 0.5), (3.0, 0.5)]);
+        assert!(path_crosses_obstacle(&obstacle_polygons, &through));
+        let tangent = points(&[(-3.0, 1.0), (3.0, 1.0)]);
+        assert!(!path_crosses_obstacle(&obstacle_polygons, &tangent));
+    }
+
+    #[test]
+    fn flags_long_and_missing_paths() {
+        let comparison = |theta_star_length, exact_length, theta_star_clear| QueryComparison {
+            start: Vec3::ZERO,
+            goal: Vec3::X,
+            theta_star_length,
+            exact_length,
+            theta_star_clear,
+        };
+        let report = OptimalityReport {
+            comparisons: vec![
+                comparison(Some(10.0), Some(10.0), true),
+                comparison(Some(11.0), Some(10.0), true),
+                comparison(None, Some(10.0), true),
+                comparison(Some(10.0), Some(10.0), false),
+                comparison(None, None, true),
+            ],
+            tolerance: 0.05,
+        };
+        let issues: Vec<OptimalityIssue> = report
+            .flagged()
+            .into_iter()
+            .map(|(_, issue)| issue)
+            .collect();
+        assert_eq!(
+            issues,
+            vec![
+                OptimalityIssue::Suboptimal { ratio: 1.1 },
+                OptimalityIssue::MissedPath,
+                OptimalityIssue::CrossesObstacle,
+            ]
+        );
+    }
+}
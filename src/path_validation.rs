@@ -0,0 +1,164 @@
+use crate::command_queue::CommandQueue;
+use crate::obstacles::ObstaclePolygons;
+use crate::pathfinding::{find_path, is_route_clear, NavMesh, PathQueryStats};
+use crate::player::{GizmoPath, TargetPosition};
+use bevy::prelude::*;
+
+/// How often remaining paths are checked even without an obstacle change.
+pub const PATH_VALIDATION_INTERVAL: f32 = 1.0;
+
+/// Sent by anything that adds, moves or removes obstacles.
+#[derive(Event)]
+pub struct ObstaclesChanged;
+
+/// Sent when an agent's remaining route was found blocked and had to be replanned.
+#[derive(Event)]
+pub struct PathInvalidated {
+    pub entity: Entity,
+}
+
+/// Asks every agent to plan its current route and queued legs afresh, for example after
+/// the whole level was swapped out underneath them.
+#[derive(Event)]
+pub struct RepathAllAgents;
+
+#[derive(Resource)]
+pub struct PathValidationTimer(pub Timer);
+
+impl Default for PathValidationTimer {
+    fn default() -> Self {
+        Self(Timer::from_seconds(
+            PATH_VALIDATION_INTERVAL,
+            TimerMode::Repeating,
+        ))
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn validate_paths(
+    mut obstacles_changed: EventReader<ObstaclesChanged>,
+    mut path_invalidated: EventWriter<PathInvalidated>,
+    mut validation_timer: ResMut<PathValidationTimer>,
+    mut agent_query: Query<(
+        Entity,
+        &Transform,
+        &mut TargetPosition,
+        Option<&mut GizmoPath>,
+        Option<&mut CommandQueue>,
+    )>,
+    obstacle_polygons: Res<ObstaclePolygons>,
+    nav_mesh: Res<NavMesh>,
+    mut query_stats: Deferred<PathQueryStats>,
+    time: Res<Time>,
+) {
+    let periodic = validation_timer.0.tick(time.delta()).just_finished();
+    let changed = obstacles_changed.read().count() > 0 || obstacle_polygons.is_changed();
+    if !periodic && !changed {
+        return;
+    }
+
+    for (entity, transform, mut target_position, gizmo_path, command_queue) in &mut agent_query {
+        let mut invalidated = false;
+
+        if let Some(path) = &target_position.0 {
+            if !path.is_empty() && !is_route_clear(&obstacle_polygons, transform.translation, path)
+            {
+                let goal = path[path.len() - 1];
+                let new_path = find_path(
+                    &nav_mesh,
+                    &obstacle_polygons,
+                    transform.translation,
+                    goal,
+                    &mut query_stats,
+                );
+
+                if let Some(mut gizmo_path) = gizmo_path {
+                    gizmo_path.0.clone_from(&new_path);
+                }
+                target_position.0 = new_path;
+                invalidated = true;
+            }
+        }
+
+        // Queued legs start where the previous leg ends, so they are replanned in place
+        if let Some(mut command_queue) = command_queue {
+            for leg in command_queue.legs.iter_mut() {
+                if is_route_clear(&obstacle_polygons, leg.start, &leg.path) {
+                    continue;
+                }
+
+                if let Some(path) = find_path(
+                    &nav_mesh,
+                    &obstacle_polygons,
+                    leg.start,
+                    leg.destination,
+                    &mut query_stats,
+                ) {
+                    leg.path = path;
+                }
+                invalidated = true;
+            }
+        }
+
+        if invalidated {
+            path_invalidated.send(PathInvalidated { entity });
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn repath_all_agents(
+    mut repath_all_agents: EventReader<RepathAllAgents>,
+    mut agent_query: Query<(
+        &Transform,
+        &mut TargetPosition,
+        Option<&mut GizmoPath>,
+        Option<&mut CommandQueue>,
+    )>,
+    obstacle_polygons: Res<ObstaclePolygons>,
+    nav_mesh: Res<NavMesh>,
+    mut query_stats: Deferred<PathQueryStats>,
+) {
+    if repath_all_agents.read().count() == 0 {
+        return;
+    }
+
+    for (transform, mut target_position, gizmo_path, command_queue) in &mut agent_query {
+        if let Some(&goal) = target_position.0.as_ref().and_then(|path| path.last()) {
+            let new_path = find_path(
+                &nav_mesh,
+                &obstacle_polygons,
+                transform.translation,
+                goal,
+                &mut query_stats,
+            );
+            if let Some(mut gizmo_path) = gizmo_path {
+                gizmo_path.0.clone_from(&new_path);
+            }
+            target_position.0 = new_path;
+        }
+
+        if let Some(mut command_queue) = command_queue {
+            for leg in command_queue.legs.iter_mut() {
+                if let Some(path) = find_path(
+                    &nav_mesh,
+                    &obstacle_polygons,
+                    leg.start,
+                    leg.destination,
+                    &mut query_stats,
+                ) {
+                    leg.path = path;
+                }
+            }
+        }
+    }
+}
+
+pub fn report_invalidated_paths(mut path_invalidated: EventReader<PathInvalidated>) {
+    for event in path_invalidated.read() {
+        println!(
+            "Path of {:?} was blocked and has been replanned.",
+            event.entity
+        );
+    }
+}
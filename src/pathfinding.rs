@@ -1,9 +1,14 @@
-use bevy::prelude::Resource;
+use bevy::ecs::system::{SystemBuffer, SystemMeta};
+use bevy::prelude::{Resource, Vec3, World};
 use std::cmp::Ordering;
-use std::collections::{BinaryHeap, HashMap};
+use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::f32;
+use std::time::{Duration, Instant};
 
-use crate::utils::{line_intersects_polygon_with_vertex_check, Point, Polygon};
+use crate::obstacles::ObstaclePolygons;
+use crate::utils::{
+    does_line_intersect_polygon, line_intersects_polygon_with_vertex_check, Point, Polygon,
+};
 
 struct Node {
     point: Point,
@@ -34,24 +39,58 @@ impl PartialOrd for Node {
     }
 }
 
-#[derive(Debug, Clone, Resource)]
+/// The search `find_path` runs over the nav mesh.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Planner {
+    /// Theta*: a vertex may link straight to the parent of the vertex being expanded,
+    /// which gives any-angle paths.
+    #[default]
+    ThetaStar,
+    /// A* over the visibility graph, only ever linking to the vertex being expanded.
+    AStar,
+    /// Dijkstra over the full visibility graph: the shortest path through the nav mesh
+    /// vertices, at the price of testing sight lines from every vertex it settles.
+    Exact,
+}
+
+#[derive(Debug, Clone, Resource, Default)]
 pub struct NavMesh {
     pub vertices: Vec<Point>,
+    pub planner: Planner,
 }
 
 impl NavMesh {
     pub fn new() -> Self {
         NavMesh {
             vertices: Vec::new(),
+            planner: Planner::default(),
         }
     }
 
-    pub fn add_vertex(&mut self, point: Point) {
-        self.vertices.push(point);
+    pub fn with_planner(mut self, planner: Planner) -> Self {
+        self.planner = planner;
+        self
+    }
+
+    /// Builds a nav mesh from the corners of every obstacle that stick out into
+    /// walkable space; reflex corners of concave obstacles are left out. A corner
+    /// shared by polygons that went through `weld_vertices` becomes a single vertex;
+    /// unwelded corners only merge when they fall in the same `POINT_QUANTUM` cell.
+    pub fn from_polygons(obstacle_polygons: &ObstaclePolygons) -> Self {
+        let mut nav_mesh = NavMesh::new();
+        let mut seen = HashSet::new();
+        for polygon in &obstacle_polygons.polygons {
+            for (i, vertex) in polygon.vertices.iter().enumerate() {
+                if polygon.is_salient_corner(i) && seen.insert(vertex.clone()) {
+                    nav_mesh.add_vertex(vertex.clone());
+                }
+            }
+        }
+        nav_mesh
     }
 
-    pub fn remove_vertex(&mut self, point: &Point) {
-        self.vertices.retain(|v| v != point);
+    pub fn add_vertex(&mut self, point: Point) {
+        self.vertices.push(point);
     }
 }
 
@@ -59,109 +98,622 @@ fn heuristic(p1: &Point, p2: &Point) -> f32 {
     ((p1.x - p2.x).powi(2) + (p1.y - p2.y).powi(2) + (p1.z - p2.z).powi(2)).sqrt()
 }
 
-fn line_of_sight(s: &Point, s_prime: &Point, polygons: &[Polygon]) -> bool {
-    for polygon in polygons {
-        if line_intersects_polygon_with_vertex_check(s, s_prime, polygon) {
-            return false;
+/// A line-of-sight check that failed during a search, and the polygon that blocked it.
+#[derive(Debug, Clone)]
+pub struct BlockedSightLine {
+    pub from: Point,
+    pub to: Point,
+    pub polygon: usize,
+}
+
+/// A parent link Theta* weighed for a vertex, and whether it improved the vertex's
+/// score.
+#[derive(Debug, Clone)]
+pub struct ParentLink {
+    pub child: Point,
+    pub parent: Point,
+    pub accepted: bool,
+}
+
+/// Everything a single `theta_star` query did, recorded for the debug overlay.
+#[derive(Debug, Clone, Default)]
+pub struct SearchTrace {
+    pub expanded: Vec<Point>,
+    pub parent_links: Vec<ParentLink>,
+    pub blocked_sight_lines: Vec<BlockedSightLine>,
+}
+
+/// Returns the index of the first polygon that blocks the segment, if any.
+pub fn first_blocking_polygon(s: &Point, s_prime: &Point, polygons: &[Polygon]) -> Option<usize> {
+    polygons
+        .iter()
+        .position(|polygon| line_intersects_polygon_with_vertex_check(s, s_prime, polygon))
+}
+
+pub fn line_of_sight(s: &Point, s_prime: &Point, polygons: &[Polygon]) -> bool {
+    first_blocking_polygon(s, s_prime, polygons).is_none()
+}
+
+/// Work counters of a single search.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SearchStats {
+    pub nodes_expanded: u32,
+    pub los_tests: u32,
+    pub los_cache_hits: u32,
+}
+
+/// Totals over `find_path` queries. As a resource it holds those of the whole app since
+/// the diagnostics last took them; systems record theirs through
+/// `Deferred<PathQueryStats>`, which adds them to the resource when the system's
+/// commands are applied.
+#[derive(Resource, Debug, Clone, Copy, Default)]
+pub struct PathQueryStats {
+    pub queries: u32,
+    pub query_time: Duration,
+    pub search: SearchStats,
+}
+
+impl PathQueryStats {
+    fn record(&mut self, query_time: Duration, search: SearchStats) {
+        self.queries += 1;
+        self.query_time += query_time;
+        self.search.nodes_expanded += search.nodes_expanded;
+        self.search.los_tests += search.los_tests;
+        self.search.los_cache_hits += search.los_cache_hits;
+    }
+
+    pub fn add(&mut self, other: &PathQueryStats) {
+        self.queries += other.queries;
+        self.query_time += other.query_time;
+        self.search.nodes_expanded += other.search.nodes_expanded;
+        self.search.los_tests += other.search.los_tests;
+        self.search.los_cache_hits += other.search.los_cache_hits;
+    }
+}
+
+impl SystemBuffer for PathQueryStats {
+    fn apply(&mut self, _system_meta: &SystemMeta, world: &mut World) {
+        let recorded = std::mem::take(self);
+        if let Some(mut stats) = world.get_resource_mut::<PathQueryStats>() {
+            stats.add(&recorded);
         }
     }
-    true
 }
 
-pub fn theta_star(
-    mesh: &mut NavMesh,
+/// Line of sight through a per-search cache. Theta* asks about the same pairs over and
+/// over (a vertex against its parent, and the reverse), so answers are kept for both
+/// directions. Failed checks are written to the trace the first time they are made.
+fn cached_sight(
+    from: &Point,
+    to: &Point,
+    obstacle_polygons: &[Polygon],
+    cache: &mut HashMap<(Point, Point), Option<usize>>,
+    stats: &mut SearchStats,
+    trace: &mut Option<&mut SearchTrace>,
+) -> bool {
+    let key = (from.clone(), to.clone());
+    if let Some(blocker) = cache.get(&key) {
+        stats.los_cache_hits += 1;
+        return blocker.is_none();
+    }
+
+    stats.los_tests += 1;
+    let blocker = first_blocking_polygon(from, to, obstacle_polygons);
+    cache.insert(key, blocker);
+    cache.insert((to.clone(), from.clone()), blocker);
+
+    if let (Some(polygon), Some(trace)) = (blocker, trace) {
+        trace.blocked_sight_lines.push(BlockedSightLine {
+            from: from.clone(),
+            to: to.clone(),
+            polygon,
+        });
+    }
+    blocker.is_none()
+}
+
+/// How far a `ThetaStarSearch` has got.
+#[derive(Debug, Clone)]
+pub enum SearchStatus {
+    Running,
+    Found(Vec<Point>),
+    Exhausted,
+}
+
+/// What a single expansion of `ThetaStarSearch` did.
+#[derive(Debug, Clone)]
+pub struct ExpansionStep {
+    pub current: Point,
+    /// Links that improved a vertex's score during this expansion.
+    pub links: Vec<ParentLink>,
+}
+
+/// Theta* as a resumable state machine, so a query can be advanced one expansion at a
+/// time. `theta_star` runs it to the end in one go.
+pub struct ThetaStarSearch {
+    vertices: Vec<Point>,
     start: Point,
     goal: Point,
-    obstacle_polygons: &[Polygon],
-) -> Vec<Point> {
-    mesh.add_vertex(start.clone());
-    mesh.add_vertex(goal.clone());
+    open_list: BinaryHeap<Node>,
+    came_from: HashMap<Point, Point>,
+    g_score: HashMap<Point, f32>,
+    current: Option<Point>,
+    status: SearchStatus,
+    sight_cache: HashMap<(Point, Point), Option<usize>>,
+    stats: SearchStats,
+    any_angle: bool,
+}
 
-    let mut open_list = BinaryHeap::new();
-    let mut came_from: HashMap<Point, Point> = HashMap::new();
-    let mut g_score: HashMap<Point, f32> = HashMap::new();
-    let mut f_score: HashMap<Point, f32> = HashMap::new();
+impl ThetaStarSearch {
+    pub fn new(mesh: &NavMesh, start: Point, goal: Point) -> Self {
+        let mut vertices = mesh.vertices.clone();
+        vertices.push(start.clone());
+        vertices.push(goal.clone());
+
+        let mut open_list = BinaryHeap::new();
+        let mut came_from: HashMap<Point, Point> = HashMap::new();
+        let mut g_score: HashMap<Point, f32> = HashMap::new();
+
+        let inf = f32::INFINITY;
+
+        for vertex in &vertices {
+            g_score.insert(vertex.clone(), inf);
+        }
+
+        g_score.insert(start.clone(), 0.0);
 
-    let inf = f32::INFINITY;
+        open_list.push(Node {
+            point: start.clone(),
+            g_score: 0.0,
+            f_score: heuristic(&start, &goal),
+        });
 
-    for vertex in &mesh.vertices {
-        g_score.insert(vertex.clone(), inf);
-        f_score.insert(vertex.clone(), inf);
+        came_from.insert(start.clone(), start.clone());
+
+        ThetaStarSearch {
+            vertices,
+            start,
+            goal,
+            open_list,
+            came_from,
+            g_score,
+            current: None,
+            status: SearchStatus::Running,
+            sight_cache: HashMap::new(),
+            stats: SearchStats::default(),
+            any_angle: mesh.planner == Planner::ThetaStar,
+        }
     }
 
-    g_score.insert(start.clone(), 0.0);
-    f_score.insert(start.clone(), heuristic(&start, &goal));
+    pub fn status(&self) -> &SearchStatus {
+        &self.status
+    }
 
-    open_list.push(Node {
-        point: start.clone(),
-        g_score: 0.0,
-        f_score: heuristic(&start, &goal),
-    });
+    /// The vertex expanded by the most recent step.
+    pub fn current(&self) -> Option<&Point> {
+        self.current.as_ref()
+    }
 
-    came_from.insert(start.clone(), start.clone());
+    pub fn stats(&self) -> SearchStats {
+        self.stats
+    }
 
-    while let Some(Node {
-        point: current,
-        g_score: current_g_score,
-        ..
-    }) = open_list.pop()
-    {
-        if current == goal {
+    pub fn start(&self) -> &Point {
+        &self.start
+    }
+
+    pub fn goal(&self) -> &Point {
+        &self.goal
+    }
+
+    /// The open list as `(point, g, f)`, best first. Entries superseded by a cheaper
+    /// route to the same vertex are left out.
+    pub fn open_nodes(&self) -> Vec<(Point, f32, f32)> {
+        let mut nodes: Vec<(Point, f32, f32)> = self
+            .open_list
+            .iter()
+            .filter(|node| self.g_score.get(&node.point) == Some(&node.g_score))
+            .map(|node| (node.point.clone(), node.g_score, node.f_score))
+            .collect();
+        nodes.sort_by(|a, b| a.2.total_cmp(&b.2));
+        nodes.dedup_by(|a, b| a.0 == b.0);
+        nodes
+    }
+
+    /// Every vertex reached so far, paired with its current parent.
+    pub fn parent_links(&self) -> impl Iterator<Item = (&Point, &Point)> {
+        self.came_from
+            .iter()
+            .filter(|(child, parent)| child != parent)
+    }
+
+    /// Expands the best vertex on the open list. Returns `None` once the search has
+    /// finished.
+    pub fn step(
+        &mut self,
+        obstacle_polygons: &[Polygon],
+        mut trace: Option<&mut SearchTrace>,
+    ) -> Option<ExpansionStep> {
+        if !matches!(self.status, SearchStatus::Running) {
+            return None;
+        }
+
+        let Some(Node {
+            point: current,
+            g_score: current_g_score,
+            ..
+        }) = self.open_list.pop()
+        else {
+            self.status = SearchStatus::Exhausted;
+            return None;
+        };
+
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.expanded.push(current.clone());
+        }
+        self.current = Some(current.clone());
+        self.stats.nodes_expanded += 1;
+
+        let mut step = ExpansionStep {
+            current: current.clone(),
+            links: Vec::new(),
+        };
+
+        if current == self.goal {
             let mut path = Vec::new();
             let mut current = current;
-            while let Some(prev) = came_from.get(&current) {
+            while let Some(prev) = self.came_from.get(&current) {
                 if &current == prev {
                     break;
                 }
                 path.push(current.clone());
                 current = prev.clone();
             }
-            path.push(start.clone());
+            path.push(self.start.clone());
             path.reverse();
 
-            mesh.remove_vertex(&start);
-            mesh.remove_vertex(&goal);
-
-            return path;
-        }
-
-        for neighbor in &mesh.vertices {
-            if neighbor != &current && line_of_sight(&current, neighbor, obstacle_polygons) {
-                let parent = came_from.get(&current).unwrap_or(&current).clone();
-
-                if line_of_sight(&parent, neighbor, obstacle_polygons) {
-                    let tentative_g_score = g_score[&parent] + heuristic(&parent, neighbor);
-                    if tentative_g_score < g_score[neighbor] {
-                        came_from.insert(neighbor.clone(), parent.clone());
-                        g_score.insert(neighbor.clone(), tentative_g_score);
-                        let new_f_score = tentative_g_score + heuristic(neighbor, &goal);
-                        f_score.insert(neighbor.clone(), new_f_score);
-                        open_list.push(Node {
-                            point: neighbor.clone(),
-                            g_score: tentative_g_score,
-                            f_score: new_f_score,
-                        });
-                    }
+            self.status = SearchStatus::Found(path);
+            return Some(step);
+        }
+
+        for neighbor in &self.vertices {
+            if neighbor != &current
+                && cached_sight(
+                    &current,
+                    neighbor,
+                    obstacle_polygons,
+                    &mut self.sight_cache,
+                    &mut self.stats,
+                    &mut trace,
+                )
+            {
+                let parent = self.came_from.get(&current).unwrap_or(&current).clone();
+
+                // Path 2 connects the neighbor straight to the current vertex's parent,
+                // path 1 goes through the current vertex
+                let (link_parent, tentative_g_score) = if self.any_angle
+                    && cached_sight(
+                        &parent,
+                        neighbor,
+                        obstacle_polygons,
+                        &mut self.sight_cache,
+                        &mut self.stats,
+                        &mut trace,
+                    ) {
+                    let score = self.g_score[&parent] + heuristic(&parent, neighbor);
+                    (parent, score)
                 } else {
-                    let tentative_g_score = current_g_score + heuristic(&current, neighbor);
-                    if tentative_g_score < g_score[neighbor] {
-                        came_from.insert(neighbor.clone(), current.clone());
-                        g_score.insert(neighbor.clone(), tentative_g_score);
-                        let new_f_score = tentative_g_score + heuristic(neighbor, &goal);
-                        f_score.insert(neighbor.clone(), new_f_score);
-                        open_list.push(Node {
-                            point: neighbor.clone(),
-                            g_score: tentative_g_score,
-                            f_score: new_f_score,
-                        });
-                    }
+                    let score = current_g_score + heuristic(&current, neighbor);
+                    (current.clone(), score)
+                };
+
+                let accepted = tentative_g_score < self.g_score[neighbor];
+                let link = ParentLink {
+                    child: neighbor.clone(),
+                    parent: link_parent.clone(),
+                    accepted,
+                };
+                if let Some(trace) = trace.as_deref_mut() {
+                    trace.parent_links.push(link.clone());
+                }
+
+                if accepted {
+                    step.links.push(link);
+                    self.came_from.insert(neighbor.clone(), link_parent);
+                    self.g_score.insert(neighbor.clone(), tentative_g_score);
+                    let new_f_score = tentative_g_score + heuristic(neighbor, &self.goal);
+                    self.open_list.push(Node {
+                        point: neighbor.clone(),
+                        g_score: tentative_g_score,
+                        f_score: new_f_score,
+                    });
                 }
             }
         }
+
+        Some(step)
+    }
+
+    /// Steps until the search finishes and returns the path, empty when there is none.
+    pub fn run(
+        &mut self,
+        obstacle_polygons: &[Polygon],
+        mut trace: Option<&mut SearchTrace>,
+    ) -> Vec<Point> {
+        while self.step(obstacle_polygons, trace.as_deref_mut()).is_some() {}
+
+        match &self.status {
+            SearchStatus::Found(path) => path.clone(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Any-angle search over the nav mesh vertices, returning the path (empty when there is
+/// none) and the work it took. When a `trace` is given, the expansions, parent links
+/// and failed line-of-sight checks are recorded into it.
+pub fn theta_star(
+    mesh: &NavMesh,
+    start: Point,
+    goal: Point,
+    obstacle_polygons: &[Polygon],
+    trace: Option<&mut SearchTrace>,
+) -> (Vec<Point>, SearchStats) {
+    let mut search = ThetaStarSearch::new(mesh, start, goal);
+    let path = search.run(obstacle_polygons, trace);
+    (path, search.stats())
+}
+
+/// Dijkstra over the visibility graph of the nav mesh vertices, returning the shortest
+/// path (empty when there is none) and the work it took. Edges are the sight lines
+/// between vertices, tested as each vertex is settled. A `trace` is filled in as for
+/// `theta_star`.
+pub fn visibility_dijkstra(
+    mesh: &NavMesh,
+    start: Point,
+    goal: Point,
+    obstacle_polygons: &[Polygon],
+    mut trace: Option<&mut SearchTrace>,
+) -> (Vec<Point>, SearchStats) {
+    let mut vertices = mesh.vertices.clone();
+    vertices.push(start.clone());
+    vertices.push(goal.clone());
+
+    let mut stats = SearchStats::default();
+    let mut sight_cache = HashMap::new();
+    let mut distance: HashMap<Point, f32> = HashMap::from([(start.clone(), 0.0)]);
+    let mut came_from: HashMap<Point, Point> = HashMap::new();
+    let mut settled: HashSet<Point> = HashSet::new();
+    let mut open_list = BinaryHeap::from([Node {
+        point: start.clone(),
+        g_score: 0.0,
+        f_score: 0.0,
+    }]);
+
+    while let Some(Node {
+        point: current,
+        g_score: current_distance,
+        ..
+    }) = open_list.pop()
+    {
+        if !settled.insert(current.clone()) {
+            continue;
+        }
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.expanded.push(current.clone());
+        }
+        stats.nodes_expanded += 1;
+
+        if current == goal {
+            let mut path = vec![current.clone()];
+            let mut current = current;
+            while let Some(previous) = came_from.get(&current) {
+                path.push(previous.clone());
+                current = previous.clone();
+            }
+            path.reverse();
+            return (path, stats);
+        }
+
+        for neighbor in &vertices {
+            if settled.contains(neighbor)
+                || !cached_sight(
+                    &current,
+                    neighbor,
+                    obstacle_polygons,
+                    &mut sight_cache,
+                    &mut stats,
+                    &mut trace,
+                )
+            {
+                continue;
+            }
+
+            let tentative = current_distance + heuristic(&current, neighbor);
+            let accepted = distance
+                .get(neighbor)
+                .is_none_or(|&known| tentative < known);
+            if let Some(trace) = trace.as_deref_mut() {
+                trace.parent_links.push(ParentLink {
+                    child: neighbor.clone(),
+                    parent: current.clone(),
+                    accepted,
+                });
+            }
+            if accepted {
+                distance.insert(neighbor.clone(), tentative);
+                came_from.insert(neighbor.clone(), current.clone());
+                open_list.push(Node {
+                    point: neighbor.clone(),
+                    g_score: tentative,
+                    f_score: tentative,
+                });
+            }
+        }
+    }
+
+    (Vec::new(), stats)
+}
+
+/// Runs the planner the nav mesh is set up with.
+fn search(
+    mesh: &NavMesh,
+    start: Point,
+    goal: Point,
+    obstacle_polygons: &[Polygon],
+    trace: Option<&mut SearchTrace>,
+) -> (Vec<Point>, SearchStats) {
+    match mesh.planner {
+        Planner::ThetaStar | Planner::AStar => {
+            theta_star(mesh, start, goal, obstacle_polygons, trace)
+        }
+        Planner::Exact => visibility_dijkstra(mesh, start, goal, obstacle_polygons, trace),
+    }
+}
+
+/// Total length of a path.
+pub fn path_length(path: &[Vec3]) -> f32 {
+    path.windows(2)
+        .map(|segment| segment[0].distance(segment[1]))
+        .sum()
+}
+
+/// Returns true when the straight segment from `start` to `goal` crosses no obstacle.
+pub fn is_direct_path_clear(obstacle_polygons: &ObstaclePolygons, start: Vec3, goal: Vec3) -> bool {
+    let start = Point::from(start);
+    let goal = Point::from(goal);
+    !obstacle_polygons
+        .polygons
+        .iter()
+        .any(|polygon| does_line_intersect_polygon(&start, &goal, polygon))
+}
+
+/// Plans a path from `start` to `goal`. The search is skipped when the straight line is
+/// clear, and restricted to a single polygon when only one blocks the way.
+/// Returns `None` when no path exists. The query is counted in `query_stats`.
+pub fn find_path(
+    nav_mesh: &NavMesh,
+    obstacle_polygons: &ObstaclePolygons,
+    start: Vec3,
+    goal: Vec3,
+    query_stats: &mut PathQueryStats,
+) -> Option<Vec<Vec3>> {
+    find_path_traced(nav_mesh, obstacle_polygons, start, goal, query_stats, None)
+}
+
+/// `find_path` that records the search into `trace`. Blocking polygons in the trace are
+/// indices into `obstacle_polygons`.
+pub fn find_path_traced(
+    nav_mesh: &NavMesh,
+    obstacle_polygons: &ObstaclePolygons,
+    start: Vec3,
+    goal: Vec3,
+    query_stats: &mut PathQueryStats,
+    mut trace: Option<&mut SearchTrace>,
+) -> Option<Vec<Vec3>> {
+    let start_time = Instant::now();
+    let start_point = Point::from(start);
+    let goal_point = Point::from(goal);
+
+    if let Some(trace) = trace.as_deref_mut() {
+        *trace = SearchTrace::default();
+    }
+
+    // Option to hold the first intersecting polygon
+    let mut first_intersecting_polygon: Option<(usize, &Polygon)> = None;
+    let mut multiple_intersections = false;
+
+    for (index, polygon) in obstacle_polygons.polygons.iter().enumerate() {
+        if does_line_intersect_polygon(&start_point, &goal_point, polygon) {
+            if first_intersecting_polygon.is_some() {
+                multiple_intersections = true;
+                break;
+            } else {
+                first_intersecting_polygon = Some((index, polygon));
+            }
+        }
+    }
+
+    let Some((first_intersecting_index, first_intersecting_polygon)) = first_intersecting_polygon
+    else {
+        query_stats.record(start_time.elapsed(), SearchStats::default());
+        return Some(vec![goal]);
+    };
+
+    // Decide whether to use a single polygon or all polygons
+    let (path, mut stats) = if multiple_intersections {
+        search(
+            nav_mesh,
+            start_point,
+            goal_point,
+            &obstacle_polygons.polygons,
+            trace.as_deref_mut(),
+        )
+    } else {
+        let result = search(
+            nav_mesh,
+            start_point,
+            goal_point,
+            std::slice::from_ref(first_intersecting_polygon),
+            trace.as_deref_mut(),
+        );
+        if let Some(trace) = trace.as_deref_mut() {
+            for blocked in &mut trace.blocked_sight_lines {
+                blocked.polygon = first_intersecting_index;
+            }
+        }
+        result
+    };
+
+    // A search against a single polygon can route through its neighbours, in which case
+    // it is repeated against all of them
+    let path = if !multiple_intersections
+        && !path.is_empty()
+        && !is_path_clear(obstacle_polygons, &path)
+    {
+        if let Some(trace) = trace.as_deref_mut() {
+            *trace = SearchTrace::default();
+        }
+        let (path, retry_stats) = search(
+            nav_mesh,
+            Point::from(start),
+            Point::from(goal),
+            &obstacle_polygons.polygons,
+            trace,
+        );
+        stats.nodes_expanded += retry_stats.nodes_expanded;
+        stats.los_tests += retry_stats.los_tests;
+        stats.los_cache_hits += retry_stats.los_cache_hits;
+        path
+    } else {
+        path
+    };
+
+    query_stats.record(start_time.elapsed(), stats);
+
+    if path.is_empty() {
+        return None;
     }
 
-    mesh.remove_vertex(&start);
-    mesh.remove_vertex(&goal);
+    Some(path.iter().map(Vec3::from).collect())
+}
+
+/// Returns true when no segment of the path crosses an obstacle. Segments may touch the
+/// polygons whose corners they run between, as the paths found by `theta_star` do.
+pub fn is_path_clear(obstacle_polygons: &ObstaclePolygons, path: &[Point]) -> bool {
+    path.windows(2)
+        .all(|segment| line_of_sight(&segment[0], &segment[1], &obstacle_polygons.polygons))
+}
 
-    Vec::new()
+/// Checks the route from `position` through the remaining `waypoints`.
+pub fn is_route_clear(
+    obstacle_polygons: &ObstaclePolygons,
+    position: Vec3,
+    waypoints: &[Vec3],
+) -> bool {
+    let route: Vec<Point> = std::iter::once(position)
+        .chain(waypoints.iter().copied())
+        .map(Point::from)
+        .collect();
+    is_path_clear(obstacle_polygons, &route)
 }
@@ -0,0 +1,79 @@
+use crate::pathfinding::PathQueryStats;
+use bevy::diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic};
+use bevy::prelude::*;
+
+/// Frames of history kept for each pathfinding diagnostic.
+const HISTORY_LENGTH: usize = 120;
+
+/// Adds pathfinding diagnostics to an App: query time, nodes expanded, line-of-sight
+/// tests and cache hit rate, averaged over the queries of a frame, and the number of
+/// queries per frame.
+#[derive(Default)]
+pub struct PathfindingDiagnosticsPlugin;
+
+impl Plugin for PathfindingDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PathQueryStats>()
+            .register_diagnostic(
+                Diagnostic::new(Self::QUERY_TIME)
+                    .with_suffix("ms")
+                    .with_max_history_length(HISTORY_LENGTH),
+            )
+            .register_diagnostic(
+                Diagnostic::new(Self::NODES_EXPANDED).with_max_history_length(HISTORY_LENGTH),
+            )
+            .register_diagnostic(
+                Diagnostic::new(Self::LOS_TESTS).with_max_history_length(HISTORY_LENGTH),
+            )
+            .register_diagnostic(
+                Diagnostic::new(Self::QUERIES_PER_FRAME).with_max_history_length(HISTORY_LENGTH),
+            )
+            .register_diagnostic(
+                Diagnostic::new(Self::CACHE_HIT_RATE)
+                    .with_suffix("%")
+                    .with_max_history_length(HISTORY_LENGTH),
+            )
+            .add_systems(Last, Self::diagnostic_system);
+    }
+}
+
+impl PathfindingDiagnosticsPlugin {
+    pub const QUERY_TIME: DiagnosticPath = DiagnosticPath::const_new("pathfinding/query_time");
+    pub const NODES_EXPANDED: DiagnosticPath =
+        DiagnosticPath::const_new("pathfinding/nodes_expanded");
+    pub const LOS_TESTS: DiagnosticPath = DiagnosticPath::const_new("pathfinding/los_tests");
+    pub const QUERIES_PER_FRAME: DiagnosticPath =
+        DiagnosticPath::const_new("pathfinding/queries_per_frame");
+    pub const CACHE_HIT_RATE: DiagnosticPath =
+        DiagnosticPath::const_new("pathfinding/cache_hit_rate");
+
+    pub fn diagnostic_system(
+        mut diagnostics: Diagnostics,
+        mut query_stats: ResMut<PathQueryStats>,
+    ) {
+        let stats = std::mem::take(&mut *query_stats);
+
+        diagnostics.add_measurement(&Self::QUERIES_PER_FRAME, || stats.queries as f64);
+
+        // Frames without queries would drag the per-query averages towards zero
+        if stats.queries == 0 {
+            return;
+        }
+        let queries = stats.queries as f64;
+
+        diagnostics.add_measurement(&Self::QUERY_TIME, || {
+            stats.query_time.as_secs_f64() * 1000.0 / queries
+        });
+        diagnostics.add_measurement(&Self::NODES_EXPANDED, || {
+            stats.search.nodes_expanded as f64 / queries
+        });
+        diagnostics.add_measurement(&Self::LOS_TESTS, || stats.search.los_tests as f64 / queries);
+
+        let lookups = stats.search.los_tests + stats.search.los_cache_hits;
+        if lookups > 0 {
+            diagnostics.add_measurement(&Self::CACHE_HIT_RATE, || {
+                stats.search.los_cache_hits as f64 * 100.0 / lookups as f64
+            });
+        }
+    }
+}
@@ -1,45 +1,80 @@
+use crate::command_queue::CommandQueue;
 use crate::obstacles::ObstaclePolygons;
-use crate::pathfinding::{theta_star, NavMesh};
+use crate::pathfinding::{
+    find_path_traced, is_direct_path_clear, NavMesh, PathQueryStats, SearchTrace,
+};
 use crate::player_stats::PlayerStats;
-use crate::utils::{does_line_intersect_polygon, Point, Polygon};
+use crate::pursue::Pursue;
+use crate::search_debug::{RecordedQuery, SearchDebug};
 use bevy::prelude::*;
-use std::time::Instant;
 
 #[derive(Component)]
 pub struct Player;
 
-#[derive(Resource)]
+#[derive(Component, Default)]
 pub struct TargetPosition(pub Option<Vec<Vec3>>);
 
-#[derive(Resource)]
+#[derive(Component, Default)]
 pub struct GizmoPath(pub Option<Vec<Vec3>>);
 
-#[derive(Resource, Default)]
+#[derive(Component, Default)]
 pub struct LastTargetPosition(pub Option<Vec3>);
 
 const SIGNIFICANT_CHANGE_THRESHOLD: f32 = 0.5;
+const ARRIVAL_DISTANCE: f32 = 0.1;
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn handle_right_click_set_target_position(
+    mut commands: Commands,
     windows: Query<&Window>,
     buttons: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
     camera_query: Query<(&Camera, &GlobalTransform)>,
     ground_query: Query<&GlobalTransform, With<crate::Ground>>,
-    mut player_query: Query<(&Transform, &PlayerStats), With<Player>>,
-    mut target_position: ResMut<TargetPosition>,
-    mut gizmo_path: ResMut<GizmoPath>,
+    mut player_query: Query<
+        (
+            Entity,
+            &Transform,
+            &mut TargetPosition,
+            &mut GizmoPath,
+            &mut LastTargetPosition,
+            &mut CommandQueue,
+        ),
+        With<Player>,
+    >,
     obstacle_polygons: Res<ObstaclePolygons>,
-    mut nav_mesh: ResMut<NavMesh>,
-    mut last_target_position: ResMut<LastTargetPosition>,
+    nav_mesh: Res<NavMesh>,
+    mut query_stats: Deferred<PathQueryStats>,
+    mut search_debug: Option<ResMut<SearchDebug>>,
 ) {
     if !buttons.pressed(MouseButton::Right) {
         return;
     }
 
-    let (camera, camera_transform) = camera_query.single();
-    let ground = ground_query.single();
-    let (player_transform, _player_stats) = player_query.single_mut();
+    // Shift+right-click appends to the command queue instead of replacing the path
+    if keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        return;
+    }
+
+    let Ok((camera, camera_transform)) = camera_query.get_single() else {
+        return;
+    };
+    let Ok(ground) = ground_query.get_single() else {
+        return;
+    };
+    let Ok((
+        player,
+        player_transform,
+        mut target_position,
+        mut gizmo_path,
+        mut last_target_position,
+        mut command_queue,
+    )) = player_query.get_single_mut()
+    else {
+        return;
+    };
 
-    let cursor_position = match windows.single().cursor_position() {
+    let cursor_position = match windows.get_single().ok().and_then(Window::cursor_position) {
         Some(pos) => pos,
         None => return,
     };
@@ -57,34 +92,11 @@ pub fn handle_right_click_set_target_position(
 
     let goal_position = ray.get_point(distance);
 
-    let start_position = Point {
-        x: player_transform.translation.x,
-        y: player_transform.translation.y,
-        z: player_transform.translation.z,
-    };
-
-    let goal_point = Point {
-        x: goal_position.x,
-        y: goal_position.y,
-        z: goal_position.z,
-    };
-
-    // Option to hold the first intersecting polygon
-    let mut first_intersecting_polygon: Option<Polygon> = None;
-    let mut multiple_intersections = false;
-
-    for polygon in &obstacle_polygons.polygons {
-        if does_line_intersect_polygon(&start_position, &goal_point, polygon) {
-            if first_intersecting_polygon.is_some() {
-                multiple_intersections = true;
-                break;
-            } else {
-                first_intersecting_polygon = Some(polygon.clone());
-            }
-        }
-    }
-
-    if first_intersecting_polygon.is_some() || multiple_intersections {
+    if !is_direct_path_clear(
+        &obstacle_polygons,
+        player_transform.translation,
+        goal_position,
+    ) {
         let significant_change = match last_target_position.0 {
             Some(last_position) => {
                 goal_position.distance(last_position) > SIGNIFICANT_CHANGE_THRESHOLD
@@ -97,70 +109,87 @@ pub fn handle_right_click_set_target_position(
         }
 
         last_target_position.0 = Some(goal_position);
+    }
 
-        let start_time = Instant::now();
-
-        // Decide whether to use a single polygon or all polygons
-        let path = if multiple_intersections {
-            theta_star(
-                &mut nav_mesh,
-                start_position,
-                goal_point,
-                &obstacle_polygons.polygons,
-            )
-        } else {
-            theta_star(
-                &mut nav_mesh,
-                start_position,
-                goal_point,
-                &[first_intersecting_polygon.unwrap()],
-            )
-        };
-
-        let duration = start_time.elapsed().as_secs_f64();
-        println!("theta_star calculation took: {:?}", duration);
+    let recording = search_debug.as_ref().is_some_and(|debug| debug.enabled);
+    let mut trace = SearchTrace::default();
+    let path = find_path_traced(
+        &nav_mesh,
+        &obstacle_polygons,
+        player_transform.translation,
+        goal_position,
+        &mut query_stats,
+        recording.then_some(&mut trace),
+    );
 
-        if path.is_empty() {
-            println!("No valid path found.");
-            return;
-        }
+    if let Some(search_debug) = search_debug.as_mut().filter(|_| recording) {
+        search_debug.last_query = Some(RecordedQuery {
+            start: player_transform.translation,
+            goal: goal_position,
+            trace,
+        });
+    }
 
-        target_position.0 = Some(path.iter().map(|p| Vec3::new(p.x, p.y, p.z)).collect());
-        gizmo_path.0 = Some(path.iter().map(|p| Vec3::new(p.x, p.y, p.z)).collect());
+    let Some(path) = path else {
+        println!("No valid path found.");
         return;
-    }
+    };
 
-    target_position.0 = Some(vec![goal_position]);
-    gizmo_path.0 = Some(vec![goal_position]);
+    // A plain right-click is a new order, so queued legs and pursuits are dropped
+    command_queue.clear();
+    commands.entity(player).remove::<Pursue>();
+    target_position.0 = Some(path.clone());
+    gizmo_path.0 = Some(path);
 }
 
 pub fn move_player_towards_target(
-    mut player_query: Query<(&mut Transform, &PlayerStats), With<Player>>,
-    mut target_position: ResMut<TargetPosition>,
-    mut gizmo_path: ResMut<GizmoPath>,
+    mut player_query: Query<(
+        &mut Transform,
+        &PlayerStats,
+        &mut TargetPosition,
+        &mut GizmoPath,
+    )>,
     time: Res<Time>,
 ) {
-    let path = match &mut target_position.0 {
-        Some(path) if !path.is_empty() => path,
-        _ => return,
-    };
+    for (mut player_transform, player_stats, mut target_position, mut gizmo_path) in
+        &mut player_query
+    {
+        let path = match &mut target_position.0 {
+            Some(path) if !path.is_empty() => path,
+            _ => continue,
+        };
 
-    let (mut player_transform, player_stats) = player_query.single_mut();
+        let mut player_position_2d = Vec2::new(
+            player_transform.translation.x,
+            player_transform.translation.z,
+        );
+
+        // Spend this frame's travel distance across as many waypoints as it reaches,
+        // so a long frame cannot overshoot a waypoint and orbit around it
+        let mut remaining_distance = player_stats.speed * time.delta_seconds();
+        while let Some(target) = path.first() {
+            let target_position_2d = Vec2::new(target.x, target.z);
+            let distance = player_position_2d.distance(target_position_2d);
+
+            if distance < ARRIVAL_DISTANCE || distance <= remaining_distance {
+                player_position_2d = target_position_2d;
+                remaining_distance -= distance;
+                path.remove(0);
+                continue;
+            }
 
-    let target = path[0];
+            if remaining_distance <= 0.0 {
+                break;
+            }
 
-    let player_position_2d = Vec2::new(
-        player_transform.translation.x,
-        player_transform.translation.z,
-    );
-    let target_position_2d = Vec2::new(target.x, target.z);
+            let direction_2d = (target_position_2d - player_position_2d).normalize_or_zero();
+            player_position_2d += direction_2d * remaining_distance;
+            break;
+        }
 
-    let direction_2d = (target_position_2d - player_position_2d).normalize_or_zero();
-    player_transform.translation.x += direction_2d.x * player_stats.speed * time.delta_seconds();
-    player_transform.translation.z += direction_2d.y * player_stats.speed * time.delta_seconds();
+        player_transform.translation.x = player_position_2d.x;
+        player_transform.translation.z = player_position_2d.y;
 
-    if player_position_2d.distance(target_position_2d) < 0.1 {
-        path.remove(0);
         if path.is_empty() {
             gizmo_path.0 = None;
         }
@@ -1,28 +1,59 @@
+use crate::command_queue::CommandQueue;
 use crate::player::GizmoPath;
 use crate::Ground;
 use bevy::prelude::*;
 
 pub fn draw_path_gizmos(
-    gizmo_path: Res<GizmoPath>,
+    path_query: Query<(&GizmoPath, Option<&CommandQueue>)>,
     ground_query: Query<&GlobalTransform, With<Ground>>,
     mut gizmos: Gizmos,
 ) {
-    let ground = ground_query.single();
+    let Ok(ground) = ground_query.get_single() else {
+        return;
+    };
 
-    if let Some(path) = &gizmo_path.0 {
-        // Draw circles at each waypoint
-        for target in path {
-            gizmos.circle(*target + Vec3::Y * 0.01, ground.up(), 0.2, Color::WHITE);
+    for (gizmo_path, command_queue) in &path_query {
+        if let Some(path) = &gizmo_path.0 {
+            // Draw circles at each waypoint
+            for target in path {
+                gizmos.circle(*target + Vec3::Y * 0.01, ground.up(), 0.2, Color::WHITE);
+            }
+
+            // Draw lines connecting each waypoint
+            for window in path.windows(2) {
+                if let [start, end] = window {
+                    gizmos.line(
+                        *start + Vec3::Y * 0.01,
+                        *end + Vec3::Y * 0.01,
+                        Color::srgb(0.5, 0.0, 0.5),
+                    );
+                }
+            }
         }
 
-        // Draw lines connecting each waypoint
-        for window in path.windows(2) {
-            if let [start, end] = window {
-                gizmos.line(
-                    *start + Vec3::Y * 0.01,
-                    *end + Vec3::Y * 0.01,
-                    Color::srgb(0.5, 0.0, 0.5),
-                );
+        let Some(command_queue) = command_queue else {
+            continue;
+        };
+
+        // Queued legs are drawn in orange, or in cyan while they loop as a patrol
+        let leg_color = if command_queue.patrol {
+            Color::srgb(0.0, 0.8, 0.8)
+        } else {
+            Color::srgb(1.0, 0.6, 0.0)
+        };
+
+        for leg in &command_queue.legs {
+            gizmos.circle(
+                leg.destination + Vec3::Y * 0.01,
+                ground.up(),
+                0.3,
+                leg_color,
+            );
+
+            let mut start = leg.start;
+            for &end in &leg.path {
+                gizmos.line(start + Vec3::Y * 0.01, end + Vec3::Y * 0.01, leg_color);
+                start = end;
             }
         }
     }
@@ -1,12 +1,47 @@
-use crate::player::Player;
+use crate::command_queue::CommandQueue;
+use crate::obstacles::ObstaclePolygons;
+use crate::player::{GizmoPath, LastTargetPosition, Player, TargetPosition};
+use crate::player_stats::PlayerStats;
+use crate::pursue::Pursue;
+use crate::utils::{segment_intersection, Point};
 use bevy::prelude::*;
 
+/// How many times a single move may be deflected along an edge.
+const MAX_SLIDE_ITERATIONS: usize = 3;
+/// Distance kept between the player and an edge it ran into.
+const COLLISION_SKIN: f32 = 0.01;
+
+#[allow(clippy::type_complexity)]
 pub fn move_player_with_wasd(
+    mut commands: Commands,
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut player_query: Query<&mut Transform, With<Player>>,
+    mut player_query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &PlayerStats,
+            &mut TargetPosition,
+            &mut GizmoPath,
+            &mut LastTargetPosition,
+            &mut CommandQueue,
+        ),
+        With<Player>,
+    >,
+    obstacle_polygons: Res<ObstaclePolygons>,
     time: Res<Time>,
 ) {
-    let mut player_transform = player_query.single_mut();
+    let Ok((
+        player,
+        mut player_transform,
+        player_stats,
+        mut target_position,
+        mut gizmo_path,
+        mut last_target_position,
+        mut command_queue,
+    )) = player_query.get_single_mut()
+    else {
+        return;
+    };
 
     let mut direction = Vec3::ZERO;
 
@@ -24,7 +59,88 @@ pub fn move_player_with_wasd(
     }
 
     if direction != Vec3::ZERO {
+        // Taking direct control ends whatever the player was ordered to do
+        target_position.0 = None;
+        gizmo_path.0 = None;
+        last_target_position.0 = None;
+        command_queue.clear();
+        commands.entity(player).remove::<Pursue>();
+
         direction = direction.normalize();
-        player_transform.translation += direction * 5.0 * time.delta_seconds();
+        let delta = direction * player_stats.speed * time.delta_seconds();
+
+        let position = slide_move(
+            &obstacle_polygons,
+            Vec2::new(
+                player_transform.translation.x,
+                player_transform.translation.z,
+            ),
+            Vec2::new(delta.x, delta.z),
+        );
+        player_transform.translation.x = position.x;
+        player_transform.translation.z = position.y;
+    }
+}
+
+/// Moves from `position` by `delta` on the ground plane, stopping at obstacle edges and
+/// sliding along them with whatever movement is left. Edges are only solid from the
+/// walkable side, so a player that starts inside an obstacle can always walk out.
+pub fn slide_move(obstacle_polygons: &ObstaclePolygons, position: Vec2, delta: Vec2) -> Vec2 {
+    let mut position = position;
+    let mut remaining = delta;
+
+    for _ in 0..MAX_SLIDE_ITERATIONS {
+        let length = remaining.length();
+        if length < f32::EPSILON {
+            break;
+        }
+
+        let start = Point {
+            x: position.x,
+            y: 0.0,
+            z: position.y,
+        };
+        let end = Point {
+            x: position.x + remaining.x,
+            y: 0.0,
+            z: position.y + remaining.y,
+        };
+
+        // Find the first edge the move runs into
+        let mut first_hit: Option<(f32, Vec2, Vec2)> = None;
+        for polygon in &obstacle_polygons.polygons {
+            let n = polygon.vertices.len();
+            for i in 0..n {
+                let a = &polygon.vertices[i];
+                let b = &polygon.vertices[(i + 1) % n];
+                let edge = Vec2::new(b.x - a.x, b.z - a.z);
+                // Obstacles wind clockwise and walkable boundaries counterclockwise, so
+                // this normal points to the walkable side of either
+                let walkable_normal = Vec2::new(-edge.y, edge.x);
+
+                if remaining.dot(walkable_normal) >= 0.0 {
+                    continue;
+                }
+
+                if let Some(t) = segment_intersection(&start, &end, a, b) {
+                    if first_hit.is_none_or(|(first_t, _, _)| t < first_t) {
+                        first_hit = Some((t, edge, walkable_normal));
+                    }
+                }
+            }
+        }
+
+        let Some((t, edge, walkable_normal)) = first_hit else {
+            position += remaining;
+            break;
+        };
+
+        // Stop at the edge, backed off along its normal so that grazing moves keep the
+        // skin too, and keep the part of the move that runs along it
+        position += remaining * t + walkable_normal.normalize_or_zero() * COLLISION_SKIN;
+        let edge_direction = edge.normalize_or_zero();
+        remaining = edge_direction * (remaining * (1.0 - t)).dot(edge_direction);
     }
+
+    position
 }
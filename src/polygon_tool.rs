@@ -0,0 +1,149 @@
+use crate::cursor::CursorPosition;
+use crate::obstacle_editor::ObstacleEditor;
+use crate::obstacles::spawn_polygon_obstacle;
+use crate::utils::Polygon;
+use bevy::prelude::*;
+use rand::Rng;
+
+/// Height of the extruded mesh of a drawn obstacle.
+const POLYGON_OBSTACLE_HEIGHT: f32 = 1.0;
+/// Clicking this close to the first point closes the polygon.
+const CLOSE_DISTANCE: f32 = 0.5;
+/// Footprints smaller than this are rejected as degenerate.
+const MIN_AREA: f32 = 0.1;
+
+/// Free-form obstacle drawing, toggled with G. Left-click adds a point; clicking the
+/// first point again or pressing Enter closes the polygon, Backspace removes the last
+/// point and Escape discards the drawing.
+#[derive(Resource, Default)]
+pub struct PolygonTool {
+    pub active: bool,
+    points: Vec<Vec3>,
+}
+
+pub fn toggle_polygon_tool(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut polygon_tool: ResMut<PolygonTool>,
+    mut editor: ResMut<ObstacleEditor>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyG) {
+        polygon_tool.active = !polygon_tool.active;
+        polygon_tool.points.clear();
+        // Both tools place things with the left button
+        if polygon_tool.active {
+            editor.active = false;
+        }
+        println!(
+            "Polygon tool {}",
+            if polygon_tool.active {
+                "enabled"
+            } else {
+                "disabled"
+            }
+        );
+    }
+}
+
+/// Checks a drawn outline and brings it into the winding the obstacle polygons use.
+fn validate_footprint(points: &[Vec3]) -> Result<Polygon, String> {
+    let mut footprint = Polygon::new();
+    for point in points {
+        footprint.add_vertex(point.x, 0.0, point.z);
+    }
+
+    let footprint = footprint.validated().map_err(|error| error.to_string())?;
+    if footprint.area() < MIN_AREA {
+        return Err("it has no area".to_string());
+    }
+    Ok(footprint)
+}
+
+pub fn draw_polygon_obstacles(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    cursor_position: Res<CursorPosition>,
+    mut polygon_tool: ResMut<PolygonTool>,
+) {
+    if !polygon_tool.active {
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        polygon_tool.points.clear();
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::Backspace) {
+        polygon_tool.points.pop();
+        return;
+    }
+
+    let mut close = keyboard_input.just_pressed(KeyCode::Enter);
+    if mouse_button_input.just_pressed(MouseButton::Left) {
+        if let Some(cursor) = cursor_position.0 {
+            match polygon_tool.points.first() {
+                Some(first)
+                    if polygon_tool.points.len() >= 3
+                        && first.distance(cursor) <= CLOSE_DISTANCE =>
+                {
+                    close = true;
+                }
+                _ => polygon_tool.points.push(cursor),
+            }
+        }
+    }
+    if !close {
+        return;
+    }
+
+    match validate_footprint(&polygon_tool.points) {
+        Ok(footprint) => {
+            let mut rng = rand::thread_rng();
+            let color = Color::srgb(rng.gen::<f32>(), rng.gen::<f32>(), rng.gen::<f32>());
+            spawn_polygon_obstacle(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                footprint,
+                POLYGON_OBSTACLE_HEIGHT,
+                color,
+            );
+            polygon_tool.points.clear();
+        }
+        Err(reason) => println!("Cannot place the polygon: {reason}."),
+    }
+}
+
+pub fn draw_polygon_tool(
+    polygon_tool: Res<PolygonTool>,
+    cursor_position: Res<CursorPosition>,
+    mut gizmos: Gizmos,
+) {
+    if !polygon_tool.active || polygon_tool.points.is_empty() {
+        return;
+    }
+
+    let lift = Vec3::Y * 0.05;
+    gizmos.linestrip(
+        polygon_tool.points.iter().map(|point| *point + lift),
+        Color::srgb(1.0, 0.5, 0.0),
+    );
+    if let (Some(last), Some(cursor)) = (polygon_tool.points.last(), cursor_position.0) {
+        gizmos.line(
+            *last + lift,
+            cursor + lift,
+            Color::srgba(1.0, 0.5, 0.0, 0.4),
+        );
+    }
+    for point in &polygon_tool.points {
+        gizmos.circle(*point + lift, Dir3::Y, 0.1, Color::srgb(1.0, 0.5, 0.0));
+    }
+    gizmos.circle(
+        polygon_tool.points[0] + lift,
+        Dir3::Y,
+        CLOSE_DISTANCE,
+        Color::srgb(1.0, 0.9, 0.0),
+    );
+}
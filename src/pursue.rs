@@ -0,0 +1,216 @@
+use crate::command_queue::CommandQueue;
+use crate::cursor::CursorPosition;
+use crate::obstacles::ObstaclePolygons;
+use crate::pathfinding::{find_path, is_direct_path_clear, NavMesh, PathQueryStats};
+use crate::player::{GizmoPath, Player, TargetPosition};
+use crate::player_stats::PlayerStats;
+use bevy::prelude::*;
+
+/// How often a pursuer re-runs the path search while its target is out of sight.
+const REPATH_INTERVAL: f32 = 0.5;
+/// How far into the future a chase is allowed to predict the intercept point.
+const MAX_INTERCEPT_TIME: f32 = 3.0;
+/// How close the cursor has to be to a unit for an order to pick it.
+const PICK_RADIUS: f32 = 1.5;
+
+pub const FOLLOW_APPROACH_DISTANCE: f32 = 2.5;
+pub const CHASE_APPROACH_DISTANCE: f32 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PursueMode {
+    /// Head for the target's current position.
+    Follow,
+    /// Head for the point where the target will be met if it keeps its velocity.
+    Chase,
+}
+
+#[derive(Component)]
+pub struct Pursue {
+    pub target: Entity,
+    pub mode: PursueMode,
+    pub approach_distance: f32,
+    pub repath_timer: Timer,
+}
+
+impl Pursue {
+    pub fn new(target: Entity, mode: PursueMode, approach_distance: f32) -> Self {
+        Self {
+            target,
+            mode,
+            approach_distance,
+            repath_timer: Timer::from_seconds(REPATH_INTERVAL, TimerMode::Repeating),
+        }
+    }
+}
+
+/// Marks entities that can be followed or chased.
+#[derive(Component)]
+pub struct Pursuable;
+
+#[derive(Component, Default)]
+pub struct Velocity {
+    pub linear: Vec3,
+    last_translation: Option<Vec3>,
+}
+
+pub fn track_velocity(mut query: Query<(&Transform, &mut Velocity)>, time: Res<Time>) {
+    let delta = time.delta_seconds();
+    if delta <= 0.0 {
+        return;
+    }
+
+    for (transform, mut velocity) in &mut query {
+        if let Some(last_translation) = velocity.last_translation {
+            velocity.linear = (transform.translation - last_translation) / delta;
+        }
+        velocity.last_translation = Some(transform.translation);
+    }
+}
+
+pub fn handle_pursue_order(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    cursor_position: Res<CursorPosition>,
+    mut player_query: Query<(Entity, &mut CommandQueue), With<Player>>,
+    pursuable_query: Query<(Entity, &Transform), With<Pursuable>>,
+) {
+    let (mode, approach_distance) = if keyboard_input.just_pressed(KeyCode::KeyF) {
+        (PursueMode::Follow, FOLLOW_APPROACH_DISTANCE)
+    } else if keyboard_input.just_pressed(KeyCode::KeyC) {
+        (PursueMode::Chase, CHASE_APPROACH_DISTANCE)
+    } else {
+        return;
+    };
+
+    let Some(cursor) = cursor_position.0 else {
+        return;
+    };
+
+    // Pick the unit closest to the cursor
+    let Some((target, _)) = pursuable_query
+        .iter()
+        .map(|(entity, transform)| (entity, horizontal_distance(transform.translation, cursor)))
+        .filter(|(_, distance)| *distance < PICK_RADIUS)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+    else {
+        return;
+    };
+
+    let Ok((player, mut command_queue)) = player_query.get_single_mut() else {
+        return;
+    };
+    command_queue.clear();
+    commands
+        .entity(player)
+        .insert(Pursue::new(target, mode, approach_distance));
+}
+
+pub fn pursue_target(
+    mut commands: Commands,
+    mut pursuer_query: Query<(
+        Entity,
+        &Transform,
+        &PlayerStats,
+        &mut Pursue,
+        &mut TargetPosition,
+        &mut GizmoPath,
+    )>,
+    target_query: Query<(&Transform, Option<&Velocity>)>,
+    obstacle_polygons: Res<ObstaclePolygons>,
+    nav_mesh: Res<NavMesh>,
+    mut query_stats: Deferred<PathQueryStats>,
+    time: Res<Time>,
+) {
+    for (entity, transform, stats, mut pursue, mut target_position, mut gizmo_path) in
+        &mut pursuer_query
+    {
+        let Ok((target_transform, target_velocity)) = target_query.get(pursue.target) else {
+            // The target is gone, so the order ends where the agent stands
+            commands.entity(entity).remove::<Pursue>();
+            target_position.0 = None;
+            gizmo_path.0 = None;
+            continue;
+        };
+
+        let position = transform.translation;
+        let target = target_transform.translation;
+
+        if horizontal_distance(position, target) <= pursue.approach_distance {
+            target_position.0 = None;
+            gizmo_path.0 = None;
+            continue;
+        }
+
+        let goal = match (pursue.mode, target_velocity) {
+            (PursueMode::Chase, Some(velocity)) => {
+                intercept_point(position, stats.speed, target, velocity.linear)
+            }
+            _ => target,
+        };
+
+        pursue.repath_timer.tick(time.delta());
+
+        // With a clear line of sight there is nothing to search for
+        if is_direct_path_clear(&obstacle_polygons, position, goal) {
+            target_position.0 = Some(vec![goal]);
+            gizmo_path.0 = Some(vec![goal]);
+            continue;
+        }
+
+        let has_path = matches!(&target_position.0, Some(path) if !path.is_empty());
+        if has_path && !pursue.repath_timer.just_finished() {
+            continue;
+        }
+
+        if let Some(path) = find_path(
+            &nav_mesh,
+            &obstacle_polygons,
+            position,
+            goal,
+            &mut query_stats,
+        ) {
+            target_position.0 = Some(path.clone());
+            gizmo_path.0 = Some(path);
+        }
+    }
+}
+
+/// Predicts where an agent moving at `speed` can meet a target moving with
+/// `target_velocity`, falling back to the target's current position when it cannot.
+pub fn intercept_point(position: Vec3, speed: f32, target: Vec3, target_velocity: Vec3) -> Vec3 {
+    let offset = Vec2::new(target.x - position.x, target.z - position.z);
+    let velocity = Vec2::new(target_velocity.x, target_velocity.z);
+
+    // Solve |offset + velocity * t| = speed * t for the earliest positive t
+    let a = velocity.length_squared() - speed * speed;
+    let b = 2.0 * offset.dot(velocity);
+    let c = offset.length_squared();
+
+    let time = if a.abs() < f32::EPSILON {
+        if b < 0.0 {
+            Some(-c / b)
+        } else {
+            None
+        }
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            None
+        } else {
+            let root = discriminant.sqrt();
+            [(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)]
+                .into_iter()
+                .filter(|t| *t > 0.0)
+                .min_by(f32::total_cmp)
+        }
+    };
+
+    match time {
+        Some(time) => target + target_velocity * time.min(MAX_INTERCEPT_TIME),
+        None => target,
+    }
+}
+
+fn horizontal_distance(a: Vec3, b: Vec3) -> f32 {
+    Vec2::new(a.x, a.z).distance(Vec2::new(b.x, b.z))
+}
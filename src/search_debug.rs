@@ -0,0 +1,166 @@
+use crate::obstacles::ObstaclePolygons;
+use crate::pathfinding::{line_of_sight, NavMesh, SearchTrace};
+use crate::utils::Polygon;
+use bevy::prelude::*;
+
+/// A search recorded for the overlay, with the endpoints it was asked for.
+pub struct RecordedQuery {
+    pub start: Vec3,
+    pub goal: Vec3,
+    pub trace: SearchTrace,
+}
+
+/// Toggles and recorded data of the search debug overlay. F1 shows the last recorded
+/// query, F2 the full visibility graph and F3 the inflated obstacle outlines.
+#[derive(Resource, Default)]
+pub struct SearchDebug {
+    pub enabled: bool,
+    pub show_visibility_graph: bool,
+    pub show_obstacle_outlines: bool,
+    pub last_query: Option<RecordedQuery>,
+    visibility_edges: Option<Vec<(Vec3, Vec3)>>,
+}
+
+pub fn toggle_search_debug(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut search_debug: ResMut<SearchDebug>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F1) {
+        search_debug.enabled = !search_debug.enabled;
+        if !search_debug.enabled {
+            search_debug.last_query = None;
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::F2) {
+        search_debug.show_visibility_graph = !search_debug.show_visibility_graph;
+    }
+    if keyboard_input.just_pressed(KeyCode::F3) {
+        search_debug.show_obstacle_outlines = !search_debug.show_obstacle_outlines;
+    }
+}
+
+/// Computes the visibility graph while it is shown. Every pair of vertices is tested,
+/// so this takes a moment and is only redone when the obstacles change.
+pub fn rebuild_visibility_graph(
+    mut search_debug: ResMut<SearchDebug>,
+    nav_mesh: Res<NavMesh>,
+    obstacle_polygons: Res<ObstaclePolygons>,
+) {
+    if !search_debug.show_visibility_graph {
+        if search_debug.visibility_edges.is_some() {
+            search_debug.visibility_edges = None;
+        }
+        return;
+    }
+    if search_debug.visibility_edges.is_some() && !obstacle_polygons.is_changed() {
+        return;
+    }
+
+    let vertices = &nav_mesh.vertices;
+    let mut edges = Vec::new();
+    for (i, a) in vertices.iter().enumerate() {
+        for b in &vertices[i + 1..] {
+            if line_of_sight(a, b, &obstacle_polygons.polygons) {
+                edges.push((Vec3::from(a), Vec3::from(b)));
+            }
+        }
+    }
+    search_debug.visibility_edges = Some(edges);
+}
+
+pub fn draw_search_debug(
+    search_debug: Res<SearchDebug>,
+    obstacle_polygons: Res<ObstaclePolygons>,
+    mut gizmos: Gizmos,
+) {
+    if search_debug.show_visibility_graph {
+        if let Some(edges) = &search_debug.visibility_edges {
+            for (a, b) in edges {
+                gizmos.line(
+                    *a + Vec3::Y * 0.02,
+                    *b + Vec3::Y * 0.02,
+                    Color::srgba(0.3, 0.5, 1.0, 0.25),
+                );
+            }
+        }
+    }
+
+    if search_debug.show_obstacle_outlines {
+        for polygon in &obstacle_polygons.polygons {
+            draw_polygon_outline(&mut gizmos, polygon, 0.03, Color::srgb(0.9, 0.9, 0.9));
+        }
+    }
+
+    if !search_debug.enabled {
+        return;
+    }
+    let Some(query) = &search_debug.last_query else {
+        return;
+    };
+
+    gizmos.sphere(query.start, Quat::IDENTITY, 0.3, Color::srgb(0.0, 1.0, 0.0));
+    gizmos.sphere(query.goal, Quat::IDENTITY, 0.3, Color::srgb(1.0, 0.0, 0.0));
+
+    // Parent links Theta* considered: green when they improved a score, grey otherwise
+    for link in &query.trace.parent_links {
+        let color = if link.accepted {
+            Color::srgb(0.2, 0.9, 0.3)
+        } else {
+            Color::srgba(0.5, 0.5, 0.5, 0.3)
+        };
+        gizmos.line(
+            Vec3::from(&link.parent) + Vec3::Y * 0.05,
+            Vec3::from(&link.child) + Vec3::Y * 0.05,
+            color,
+        );
+    }
+
+    // Failed line-of-sight checks in red, with the polygon that blocked them
+    for blocked in &query.trace.blocked_sight_lines {
+        gizmos.line(
+            Vec3::from(&blocked.from) + Vec3::Y * 0.04,
+            Vec3::from(&blocked.to) + Vec3::Y * 0.04,
+            Color::srgba(1.0, 0.1, 0.1, 0.2),
+        );
+        if let Some(polygon) = obstacle_polygons.polygons.get(blocked.polygon) {
+            draw_polygon_outline(&mut gizmos, polygon, 0.06, Color::srgb(1.0, 0.3, 0.0));
+        }
+    }
+
+    // Expanded vertices in yellow, in the order they were taken off the open list
+    for vertex in &query.trace.expanded {
+        gizmos.circle(
+            Vec3::from(vertex) + Vec3::Y * 0.07,
+            Dir3::Y,
+            0.25,
+            Color::srgb(1.0, 0.9, 0.0),
+        );
+    }
+}
+
+/// Corners per full turn the shape of round obstacles is drawn with.
+const ROUND_OUTLINE_SEGMENTS: usize = 32;
+
+/// Round obstacles are drawn as their exact shape, around the outline that holds their
+/// nav vertices.
+fn draw_polygon_outline(gizmos: &mut Gizmos, polygon: &Polygon, height: f32, color: Color) {
+    if let Some(capsule) = &polygon.capsule {
+        let shape = capsule.outline(ROUND_OUTLINE_SEGMENTS, 0.0);
+        gizmos.linestrip(
+            shape
+                .vertices
+                .iter()
+                .chain(shape.vertices.first())
+                .map(|vertex| Vec3::from(vertex) + Vec3::Y * height),
+            color,
+        );
+    }
+    gizmos.linestrip(
+        polygon
+            .vertices
+            .iter()
+            .chain(polygon.vertices.first())
+            .map(|vertex| Vec3::from(vertex) + Vec3::Y * height),
+        color,
+    );
+}
@@ -0,0 +1,265 @@
+use crate::cursor::CursorPosition;
+use crate::obstacles::ObstaclePolygons;
+use crate::pathfinding::{ExpansionStep, NavMesh, SearchStatus, ThetaStarSearch};
+use crate::player::Player;
+use crate::utils::{Point, Polygon};
+use bevy::prelude::*;
+
+/// How many open list entries the panel lists.
+const OPEN_LIST_ROWS: usize = 12;
+
+/// Interactive Theta* debugger. F5 freezes the game and starts a query from the player
+/// to the cursor (or closes the debugger), F6 advances one expansion, F7 toggles
+/// running one expansion per frame and F8 runs to the end.
+#[derive(Resource, Default)]
+pub struct SearchStepper {
+    pub active: bool,
+    pub running: bool,
+    search: Option<ThetaStarSearch>,
+    obstacle_polygons: Vec<Polygon>,
+    last_step: Option<ExpansionStep>,
+    expanded: Vec<Point>,
+}
+
+impl SearchStepper {
+    fn advance(&mut self) {
+        let Some(search) = &mut self.search else {
+            return;
+        };
+        if let Some(step) = search.step(&self.obstacle_polygons, None) {
+            self.expanded.push(step.current.clone());
+            self.last_step = Some(step);
+        } else {
+            self.running = false;
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct StepperPanel;
+
+pub fn setup_stepper_panel(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                font_size: 18.0,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.0),
+            right: Val::Px(10.0),
+            ..default()
+        })
+        .with_background_color(Color::srgba(0.0, 0.0, 0.0, 0.7)),
+        StepperPanel,
+    ));
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn handle_stepper_input(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut stepper: ResMut<SearchStepper>,
+    mut time: ResMut<Time<Virtual>>,
+    cursor_position: Res<CursorPosition>,
+    player_query: Query<&Transform, With<Player>>,
+    nav_mesh: Res<NavMesh>,
+    obstacle_polygons: Res<ObstaclePolygons>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F5) {
+        if stepper.active {
+            *stepper = SearchStepper::default();
+            time.unpause();
+            return;
+        }
+
+        let Some(goal) = cursor_position.0 else {
+            return;
+        };
+        let Ok(player_transform) = player_query.get_single() else {
+            return;
+        };
+        let start = player_transform.translation;
+
+        *stepper = SearchStepper {
+            active: true,
+            running: false,
+            search: Some(ThetaStarSearch::new(
+                &nav_mesh,
+                Point::from(start),
+                Point::from(goal),
+            )),
+            obstacle_polygons: obstacle_polygons.polygons.clone(),
+            last_step: None,
+            expanded: Vec::new(),
+        };
+        time.pause();
+        return;
+    }
+
+    if !stepper.active {
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::F6) {
+        stepper.running = false;
+        stepper.advance();
+    }
+    if keyboard_input.just_pressed(KeyCode::F7) {
+        stepper.running = !stepper.running;
+    }
+    if keyboard_input.just_pressed(KeyCode::F8) {
+        while matches!(
+            stepper.search.as_ref().map(ThetaStarSearch::status),
+            Some(SearchStatus::Running)
+        ) {
+            stepper.advance();
+        }
+    }
+    if stepper.running {
+        stepper.advance();
+    }
+}
+
+pub fn update_stepper_panel(
+    stepper: Res<SearchStepper>,
+    mut panel_query: Query<(&mut Text, &mut Visibility), With<StepperPanel>>,
+) {
+    let Ok((mut text, mut visibility)) = panel_query.get_single_mut() else {
+        return;
+    };
+
+    let Some(search) = stepper.search.as_ref().filter(|_| stepper.active) else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+    *visibility = Visibility::Visible;
+
+    let status = match search.status() {
+        SearchStatus::Running if stepper.running => "running".to_string(),
+        SearchStatus::Running => "paused".to_string(),
+        SearchStatus::Found(path) => format!("found, {} waypoints", path.len()),
+        SearchStatus::Exhausted => "no path".to_string(),
+    };
+
+    let mut lines = vec![
+        "Theta* step debugger".to_string(),
+        "F6 step  F7 run/pause  F8 to end  F5 close".to_string(),
+        format!("status: {status}"),
+        format!("expansions: {}", stepper.expanded.len()),
+    ];
+
+    if let Some(current) = search.current() {
+        lines.push(format!("current: ({:.2}, {:.2})", current.x, current.z));
+    }
+
+    if let Some(step) = &stepper.last_step {
+        let rewires = step
+            .links
+            .iter()
+            .filter(|link| link.parent != step.current)
+            .count();
+        lines.push(format!(
+            "improved: {}  rewired to parent: {}",
+            step.links.len(),
+            rewires
+        ));
+    }
+
+    let open_nodes = search.open_nodes();
+    lines.push(format!("open list ({}):", open_nodes.len()));
+    for (point, g, f) in open_nodes.iter().take(OPEN_LIST_ROWS) {
+        lines.push(format!(
+            "  f {f:>7.2}  g {g:>7.2}  ({:.2}, {:.2})",
+            point.x, point.z
+        ));
+    }
+
+    text.sections[0].value = lines.join("\n");
+}
+
+pub fn draw_stepper_gizmos(stepper: Res<SearchStepper>, mut gizmos: Gizmos) {
+    if !stepper.active {
+        return;
+    }
+    let Some(search) = &stepper.search else {
+        return;
+    };
+
+    let lift = Vec3::Y * 0.08;
+
+    gizmos.sphere(
+        Vec3::from(search.start()),
+        Quat::IDENTITY,
+        0.3,
+        Color::srgb(0.0, 1.0, 0.0),
+    );
+    gizmos.sphere(
+        Vec3::from(search.goal()),
+        Quat::IDENTITY,
+        0.3,
+        Color::srgb(1.0, 0.0, 0.0),
+    );
+
+    // The search tree as it stands
+    for (child, parent) in search.parent_links() {
+        gizmos.line(
+            Vec3::from(parent) + lift,
+            Vec3::from(child) + lift,
+            Color::srgba(1.0, 1.0, 1.0, 0.4),
+        );
+    }
+
+    for vertex in &stepper.expanded {
+        gizmos.circle(
+            Vec3::from(vertex) + lift,
+            Dir3::Y,
+            0.2,
+            Color::srgb(0.4, 0.4, 0.4),
+        );
+    }
+
+    for (point, _, _) in search.open_nodes() {
+        gizmos.circle(
+            Vec3::from(&point) + lift,
+            Dir3::Y,
+            0.3,
+            Color::srgb(0.0, 0.8, 1.0),
+        );
+    }
+
+    // Links made by the last expansion: magenta where Theta* skipped to the grandparent
+    if let Some(step) = &stepper.last_step {
+        for link in &step.links {
+            let color = if link.parent != step.current {
+                Color::srgb(1.0, 0.0, 1.0)
+            } else {
+                Color::srgb(0.2, 0.9, 0.3)
+            };
+            gizmos.line(
+                Vec3::from(&link.parent) + lift * 1.5,
+                Vec3::from(&link.child) + lift * 1.5,
+                color,
+            );
+        }
+    }
+
+    if let Some(current) = search.current() {
+        gizmos.sphere(
+            Vec3::from(current) + lift,
+            Quat::IDENTITY,
+            0.4,
+            Color::srgb(1.0, 0.9, 0.0),
+        );
+    }
+
+    if let SearchStatus::Found(path) = search.status() {
+        gizmos.linestrip(
+            path.iter().map(|point| Vec3::from(point) + lift * 2.0),
+            Color::srgb(0.6, 0.0, 0.8),
+        );
+    }
+}
@@ -0,0 +1,623 @@
+use crate::level::{spawn_level_obstacles, LevelError, LevelFile};
+use crate::obstacles::ObstaclePolygons;
+use crate::pathfinding::{find_path, path_length, NavMesh, PathQueryStats};
+use crate::player::{GizmoPath, TargetPosition};
+use crate::player_stats::PlayerStats;
+use crate::stuck_detection::{PathFailed, PathProgress};
+use crate::utils::{point_in_polygon, point_segment_distance, Point, Polygon};
+use crate::{PathfindingConfig, PathfindingPlugin, PathfindingSet};
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Seconds simulated per update unless the scenario sets its own timestep.
+pub const DEFAULT_TIMESTEP: f32 = 1.0 / 60.0;
+/// Simulated seconds after which a scenario is cut short unless it sets its own limit.
+pub const DEFAULT_TIME_LIMIT: f32 = 120.0;
+/// Speed of agents that do not set their own, that of the wanderers.
+pub const DEFAULT_AGENT_SPEED: f32 = 3.0;
+/// How far an agent may reach into the inflated obstacles before it counts as a
+/// collision, so that running along their edges does not.
+const COLLISION_TOLERANCE: f32 = 0.05;
+
+/// An agent placed when the simulation starts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentSpawn {
+    pub name: String,
+    pub position: [f32; 3],
+    #[serde(default = "default_agent_speed")]
+    pub speed: f32,
+}
+
+/// Orders the named agent to `target` once `time` seconds have been simulated.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MoveCommand {
+    pub time: f32,
+    pub agent: String,
+    pub target: [f32; 3],
+}
+
+/// A scripted run: a level, the agents in it and the orders they get over time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimScenario {
+    /// The level file, relative to the scenario file. Without one the ground is open.
+    #[serde(default)]
+    pub level: Option<PathBuf>,
+    #[serde(default = "default_timestep")]
+    pub timestep: f32,
+    #[serde(default = "default_time_limit")]
+    pub time_limit: f32,
+    pub agents: Vec<AgentSpawn>,
+    #[serde(default)]
+    pub commands: Vec<MoveCommand>,
+}
+
+fn default_agent_speed() -> f32 {
+    DEFAULT_AGENT_SPEED
+}
+
+fn default_timestep() -> f32 {
+    DEFAULT_TIMESTEP
+}
+
+fn default_time_limit() -> f32 {
+    DEFAULT_TIME_LIMIT
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Level(LevelError),
+    UnknownAgent(String),
+    BadTimestep(f32),
+    BadTimeLimit(f32),
+    BadCommandTime(f32),
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScenarioError::Io(error) => write!(f, "{error}"),
+            ScenarioError::Parse(error) => write!(f, "{error}"),
+            ScenarioError::Level(error) => write!(f, "level: {error}"),
+            ScenarioError::UnknownAgent(name) => write!(f, "no agent is called `{name}`"),
+            ScenarioError::BadTimestep(timestep) => write!(f, "bad timestep {timestep}"),
+            ScenarioError::BadTimeLimit(limit) => write!(f, "bad time limit {limit}"),
+            ScenarioError::BadCommandTime(time) => write!(f, "bad command time {time}"),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+impl SimScenario {
+    pub fn from_ron(bytes: &[u8]) -> Result<Self, ScenarioError> {
+        let scenario: SimScenario = ron::de::from_bytes(bytes).map_err(ScenarioError::Parse)?;
+        if !(scenario.timestep.is_finite() && scenario.timestep > 0.0) {
+            return Err(ScenarioError::BadTimestep(scenario.timestep));
+        }
+        if !(scenario.time_limit.is_finite() && scenario.time_limit > 0.0) {
+            return Err(ScenarioError::BadTimeLimit(scenario.time_limit));
+        }
+        if let Some(command) = scenario
+            .commands
+            .iter()
+            .find(|command| !(command.time.is_finite() && command.time >= 0.0))
+        {
+            return Err(ScenarioError::BadCommandTime(command.time));
+        }
+        if let Some(command) = scenario.commands.iter().find(|command| {
+            !scenario
+                .agents
+                .iter()
+                .any(|agent| agent.name == command.agent)
+        }) {
+            return Err(ScenarioError::UnknownAgent(command.agent.clone()));
+        }
+        Ok(scenario)
+    }
+
+    /// Loads a scenario file, with its level path made relative to the working
+    /// directory.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ScenarioError> {
+        let path = path.as_ref();
+        let mut scenario = SimScenario::from_ron(&std::fs::read(path).map_err(ScenarioError::Io)?)?;
+        if let (Some(level), Some(directory)) = (&scenario.level, path.parent()) {
+            scenario.level = Some(directory.join(level));
+        }
+        Ok(scenario)
+    }
+
+    fn load_level(&self) -> Result<Option<LevelFile>, ScenarioError> {
+        let Some(path) = &self.level else {
+            return Ok(None);
+        };
+        let bytes =
+            std::fs::read(path).map_err(|error| ScenarioError::Level(LevelError::Io(error)))?;
+        LevelFile::from_ron(&bytes)
+            .map(Some)
+            .map_err(ScenarioError::Level)
+    }
+}
+
+/// How a move command ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CommandOutcome {
+    /// Still under way.
+    Pending,
+    Arrived,
+    /// No path to the target when the command was issued.
+    Unreachable,
+    /// The agent dropped its path: stuck, or the obstacles closed its way.
+    GaveUp,
+    /// A later command to the same agent came first.
+    Replaced,
+    /// Still under way when the time limit was reached.
+    TimedOut,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandReport {
+    pub issued_at: f32,
+    pub target: [f32; 3],
+    /// Length of the path planned when the command was issued.
+    pub path_length: Option<f32>,
+    pub arrived_at: Option<f32>,
+    pub travel_time: Option<f32>,
+    pub outcome: CommandOutcome,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AgentReport {
+    pub name: String,
+    pub distance_travelled: f32,
+    pub commands: Vec<CommandReport>,
+}
+
+/// What an agent ran into: an obstacle, or another agent by name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CollisionTarget {
+    Obstacle,
+    Agent(String),
+}
+
+/// The moment an agent started to overlap an obstacle or another agent.
+#[derive(Debug, Clone, Serialize)]
+pub struct CollisionRecord {
+    pub time: f32,
+    pub agent: String,
+    pub with: CollisionTarget,
+}
+
+/// An agent that gave up its path after every stall recovery failed.
+#[derive(Debug, Clone, Serialize)]
+pub struct StuckRecord {
+    pub time: f32,
+    pub agent: String,
+}
+
+/// The outcome of `run_scenario`, written out as JSON by the headless runner.
+#[derive(Debug, Clone, Serialize)]
+pub struct SimReport {
+    pub timestep: f32,
+    pub simulated_time: f32,
+    /// False when the time limit cut the run short.
+    pub completed: bool,
+    pub agents: Vec<AgentReport>,
+    pub collisions: Vec<CollisionRecord>,
+    pub stuck_events: Vec<StuckRecord>,
+}
+
+/// An agent of the scenario, by its index in the report.
+#[derive(Component)]
+pub struct SimAgent {
+    index: usize,
+    last_position: Vec3,
+    /// The command it is carrying out, by its index in the agent's report.
+    active_command: Option<usize>,
+    /// What it overlapped at the end of the previous step.
+    contacts: Vec<CollisionTarget>,
+}
+
+#[derive(Resource)]
+struct Simulation {
+    scenario: SimScenario,
+    /// Commands in the order they are issued.
+    commands: Vec<MoveCommand>,
+    next_command: usize,
+    report: SimReport,
+    finished: bool,
+}
+
+/// Runs the pathfinding and movement systems without a window, `timestep` at a time,
+/// until every command is carried out or the time limit is reached.
+pub fn run_scenario(scenario: &SimScenario) -> Result<SimReport, ScenarioError> {
+    let level = scenario.load_level()?;
+    let mut commands = scenario.commands.clone();
+    commands.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+    let timestep = Duration::from_secs_f32(scenario.timestep);
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        TransformPlugin,
+        HierarchyPlugin,
+        // Nothing is loaded from disk through the asset server, so there is nothing
+        // to watch
+        AssetPlugin {
+            watch_for_changes_override: Some(false),
+            ..default()
+        },
+    ))
+    .init_asset::<Mesh>()
+    .init_asset::<StandardMaterial>()
+    .add_plugins(PathfindingPlugin {
+        config: PathfindingConfig {
+            player_input: false,
+            level_editing: false,
+            ..default()
+        },
+    })
+    .insert_resource(TimeUpdateStrategy::ManualDuration(timestep))
+    .insert_resource(Simulation {
+        scenario: scenario.clone(),
+        commands,
+        next_command: 0,
+        report: SimReport {
+            timestep: scenario.timestep,
+            simulated_time: 0.0,
+            completed: false,
+            agents: scenario
+                .agents
+                .iter()
+                .map(|agent| AgentReport {
+                    name: agent.name.clone(),
+                    distance_travelled: 0.0,
+                    commands: Vec::new(),
+                })
+                .collect(),
+            collisions: Vec::new(),
+            stuck_events: Vec::new(),
+        },
+        finished: false,
+    })
+    .add_systems(Startup, spawn_scenario)
+    .add_systems(
+        Update,
+        issue_move_commands
+            .after(PathfindingSet::Obstacles)
+            .before(PathfindingSet::Planning),
+    )
+    .add_systems(
+        Update,
+        (record_agents, finish_simulation)
+            .chain()
+            .after(PathfindingSet::Movement),
+    );
+    if let Some(level) = level {
+        app.insert_resource(ScenarioLevel(level));
+    }
+
+    // Virtual time would otherwise clamp long steps
+    app.world_mut()
+        .resource_mut::<Time<Virtual>>()
+        .set_max_delta(timestep.max(Duration::from_millis(250)));
+    app.finish();
+    app.cleanup();
+
+    while !app.world().resource::<Simulation>().finished {
+        app.update();
+    }
+    let simulation = app.world_mut().remove_resource::<Simulation>().unwrap();
+    Ok(simulation.report)
+}
+
+#[derive(Resource)]
+struct ScenarioLevel(LevelFile);
+
+fn spawn_scenario(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    level: Option<Res<ScenarioLevel>>,
+    simulation: Res<Simulation>,
+) {
+    if let Some(level) = level {
+        spawn_level_obstacles(&mut commands, &mut meshes, &mut materials, &level.0);
+    }
+
+    for (index, agent) in simulation.scenario.agents.iter().enumerate() {
+        let position = Vec3::from_array(agent.position);
+        commands.spawn((
+            TransformBundle::from_transform(Transform::from_translation(position)),
+            SimAgent {
+                index,
+                last_position: position,
+                active_command: None,
+                contacts: Vec::new(),
+            },
+            PlayerStats::new(agent.speed, 100.0, 1.0),
+            TargetPosition::default(),
+            GizmoPath::default(),
+            PathProgress::default(),
+        ));
+    }
+}
+
+fn issue_move_commands(
+    mut simulation: ResMut<Simulation>,
+    mut agent_query: Query<(
+        &Transform,
+        &mut SimAgent,
+        &mut TargetPosition,
+        &mut GizmoPath,
+    )>,
+    obstacle_polygons: Res<ObstaclePolygons>,
+    nav_mesh: Res<NavMesh>,
+    mut query_stats: Deferred<PathQueryStats>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    let simulation = &mut *simulation;
+    while let Some(command) = simulation.commands.get(simulation.next_command) {
+        if command.time > now {
+            break;
+        }
+        simulation.next_command += 1;
+
+        let Some((transform, mut agent, mut target_position, mut gizmo_path)) = agent_query
+            .iter_mut()
+            .find(|(_, agent, _, _)| simulation.scenario.agents[agent.index].name == command.agent)
+        else {
+            continue;
+        };
+        let agent_report = &mut simulation.report.agents[agent.index];
+        let position = transform.translation;
+        let target = Vec3::from_array(command.target);
+
+        // Like a click on an unreachable spot, the order is ignored
+        let Some(path) = find_path(
+            &nav_mesh,
+            &obstacle_polygons,
+            position,
+            target,
+            &mut query_stats,
+        ) else {
+            agent_report.commands.push(CommandReport {
+                issued_at: now,
+                target: command.target,
+                path_length: None,
+                arrived_at: None,
+                travel_time: None,
+                outcome: CommandOutcome::Unreachable,
+            });
+            continue;
+        };
+
+        if let Some(active) = agent.active_command {
+            agent_report.commands[active].outcome = CommandOutcome::Replaced;
+        }
+        let mut points = vec![position];
+        points.extend(&path);
+        agent.active_command = Some(agent_report.commands.len());
+        agent_report.commands.push(CommandReport {
+            issued_at: now,
+            target: command.target,
+            path_length: Some(path_length(&points)),
+            arrived_at: None,
+            travel_time: None,
+            outcome: CommandOutcome::Pending,
+        });
+        gizmo_path.0 = Some(path.clone());
+        target_position.0 = Some(path);
+    }
+}
+
+/// How far the point reaches into the polygon, or into the ground outside a walkable
+/// boundary; zero when it is clear of it.
+fn penetration(point: &Point, polygon: &Polygon) -> f32 {
+    if point_in_polygon(point, polygon) == polygon.is_walkable_boundary() {
+        return 0.0;
+    }
+    if let Some(capsule) = &polygon.capsule {
+        return capsule.radius - point_segment_distance(point, &capsule.a, &capsule.b);
+    }
+    let count = polygon.vertices.len();
+    (0..count)
+        .map(|i| {
+            point_segment_distance(
+                point,
+                &polygon.vertices[i],
+                &polygon.vertices[(i + 1) % count],
+            )
+        })
+        .fold(f32::INFINITY, f32::min)
+}
+
+fn record_agents(
+    mut simulation: ResMut<Simulation>,
+    mut agent_query: Query<(Entity, &Transform, &mut SimAgent, &TargetPosition)>,
+    mut path_failed: EventReader<PathFailed>,
+    obstacle_polygons: Res<ObstaclePolygons>,
+    config: Res<PathfindingConfig>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    let simulation = &mut *simulation;
+    let positions: Vec<(usize, Vec3)> = agent_query
+        .iter()
+        .map(|(_, transform, agent, _)| (agent.index, transform.translation))
+        .collect();
+
+    for event in path_failed.read() {
+        if let Ok((_, _, agent, _)) = agent_query.get(event.entity) {
+            simulation.report.stuck_events.push(StuckRecord {
+                time: now,
+                agent: simulation.scenario.agents[agent.index].name.clone(),
+            });
+        }
+    }
+
+    for (_, transform, mut agent, target_position) in &mut agent_query {
+        let position = transform.translation;
+        let name = simulation.scenario.agents[agent.index].name.clone();
+        let agent_report = &mut simulation.report.agents[agent.index];
+        agent_report.distance_travelled += Vec2::new(position.x, position.z)
+            .distance(Vec2::new(agent.last_position.x, agent.last_position.z));
+        agent.last_position = position;
+
+        // Arrived agents keep an empty path, ones that gave up have theirs taken
+        if let Some(active) = agent.active_command {
+            let command = &mut agent_report.commands[active];
+            match &target_position.0 {
+                Some(path) if path.is_empty() => {
+                    command.outcome = CommandOutcome::Arrived;
+                    command.arrived_at = Some(now);
+                    command.travel_time = Some(now - command.issued_at);
+                    agent.active_command = None;
+                }
+                None => {
+                    command.outcome = CommandOutcome::GaveUp;
+                    agent.active_command = None;
+                }
+                Some(_) => {}
+            }
+        }
+
+        let point = Point::from(position);
+        let mut contacts = Vec::new();
+        if obstacle_polygons
+            .polygons
+            .iter()
+            .any(|polygon| penetration(&point, polygon) > COLLISION_TOLERANCE)
+        {
+            contacts.push(CollisionTarget::Obstacle);
+        }
+        // Agents are as wide as the clearance the nav mesh keeps from obstacles
+        for &(other, other_position) in &positions {
+            let distance = Vec2::new(position.x, position.z)
+                .distance(Vec2::new(other_position.x, other_position.z));
+            if other != agent.index && distance < 2.0 * config.agent_buffer {
+                contacts.push(CollisionTarget::Agent(
+                    simulation.scenario.agents[other].name.clone(),
+                ));
+            }
+        }
+
+        for contact in &contacts {
+            if !agent.contacts.contains(contact) {
+                simulation.report.collisions.push(CollisionRecord {
+                    time: now,
+                    agent: name.clone(),
+                    with: contact.clone(),
+                });
+            }
+        }
+        agent.contacts = contacts;
+    }
+}
+
+fn finish_simulation(
+    mut simulation: ResMut<Simulation>,
+    agent_query: Query<&SimAgent>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    simulation.report.simulated_time = now;
+
+    let commands_done = simulation.next_command == simulation.commands.len()
+        && agent_query
+            .iter()
+            .all(|agent| agent.active_command.is_none());
+    if commands_done {
+        simulation.report.completed = true;
+        simulation.finished = true;
+    } else if now >= simulation.scenario.time_limit {
+        for agent in &mut simulation.report.agents {
+            for command in &mut agent.commands {
+                if command.outcome == CommandOutcome::Pending {
+                    command.outcome = CommandOutcome::TimedOut;
+                }
+            }
+        }
+        simulation.finished = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scenario(settings: &str, time: &str) -> Result<SimScenario, ScenarioError> {
+        let text = format!(
+            "({settings} agents: [(name: \"a\", position: (0.0, 0.4, 0.0))], \
+             commands: [(time: {time}, agent: \"a\", target: (5.0, 0.4, 0.0))])"
+        );
+        SimScenario::from_ron(text.as_bytes())
+    }
+
+    #[test]
+    fn accepts_a_valid_scenario() {
+        let scenario = scenario("timestep: 0.02, time_limit: 10.0,", "0.0").unwrap();
+        assert_eq!(scenario.timestep, 0.02);
+        assert_eq!(scenario.time_limit, 10.0);
+    }
+
+    #[test]
+    fn rejects_bad_times() {
+        assert!(matches!(
+            scenario("timestep: 0.0,", "0.0"),
+            Err(ScenarioError::BadTimestep(_))
+        ));
+        assert!(matches!(
+            scenario("timestep: inf,", "0.0"),
+            Err(ScenarioError::BadTimestep(_))
+        ));
+        assert!(matches!(
+            scenario("time_limit: -1.0,", "0.0"),
+            Err(ScenarioError::BadTimeLimit(_))
+        ));
+        assert!(matches!(
+            scenario("time_limit: NaN,", "0.0"),
+            Err(ScenarioError::BadTimeLimit(_))
+        ));
+        assert!(matches!(
+            scenario("", "-0.5"),
+            Err(ScenarioError::BadCommandTime(_))
+        ));
+        assert!(matches!(
+            scenario("", "inf"),
+            Err(ScenarioError::BadCommandTime(_))
+        ));
+    }
+
+    #[test]
+    fn runs_a_move_over_open_ground() {
+        let scenario = scenario("timestep: 0.05, time_limit: 10.0,", "0.0").unwrap();
+        let report = run_scenario(&scenario).unwrap();
+        assert!(report.completed);
+        assert!(report.simulated_time < scenario.time_limit);
+        assert!(report.collisions.is_empty());
+        assert!(report.stuck_events.is_empty());
+
+        let agent = &report.agents[0];
+        assert_eq!(agent.commands.len(), 1);
+        let command = &agent.commands[0];
+        assert_eq!(command.outcome, CommandOutcome::Arrived);
+        assert!((command.path_length.unwrap() - 5.0).abs() < 1e-4);
+        // Straight across at the default speed, give or take a step
+        let expected_arrival = 5.0 / DEFAULT_AGENT_SPEED;
+        let arrived_at = command.arrived_at.unwrap();
+        assert!(
+            (arrived_at - expected_arrival).abs() <= 2.0 * scenario.timestep,
+            "{arrived_at}"
+        );
+        assert!((agent.distance_travelled - 5.0).abs() < 0.1);
+    }
+}
@@ -0,0 +1,144 @@
+use crate::command_queue::CommandQueue;
+use crate::obstacles::ObstaclePolygons;
+use crate::pathfinding::{find_path, is_route_clear, NavMesh, PathQueryStats};
+use crate::player::{GizmoPath, TargetPosition};
+use bevy::prelude::*;
+
+/// How long an agent may fail to get closer to its waypoint before it counts as stuck.
+pub const STALL_TIMEOUT: f32 = 1.5;
+/// The distance the agent has to close on its waypoint for it to count as progress.
+const MIN_PROGRESS: f32 = 0.05;
+
+/// Sent when an agent stayed stuck after every recovery attempt and gave up its path.
+#[derive(Event)]
+pub struct PathFailed {
+    pub entity: Entity,
+}
+
+/// Tracks whether an agent is still closing in on the waypoint it is heading for.
+#[derive(Component, Default)]
+pub struct PathProgress {
+    waypoint: Option<Vec3>,
+    best_distance: f32,
+    stall_time: f32,
+    recovery_attempts: u32,
+    recovering: bool,
+}
+
+impl PathProgress {
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn monitor_path_progress(
+    mut agent_query: Query<(
+        Entity,
+        &Transform,
+        &mut PathProgress,
+        &mut TargetPosition,
+        Option<&mut GizmoPath>,
+        Option<&mut CommandQueue>,
+    )>,
+    mut path_failed: EventWriter<PathFailed>,
+    obstacle_polygons: Res<ObstaclePolygons>,
+    nav_mesh: Res<NavMesh>,
+    mut query_stats: Deferred<PathQueryStats>,
+    time: Res<Time>,
+) {
+    for (entity, transform, mut progress, mut target_position, gizmo_path, command_queue) in
+        &mut agent_query
+    {
+        let Some(path) = target_position.0.as_mut().filter(|path| !path.is_empty()) else {
+            progress.reset();
+            continue;
+        };
+
+        let position = transform.translation;
+        let waypoint = path[0];
+        let distance =
+            Vec2::new(position.x, position.z).distance(Vec2::new(waypoint.x, waypoint.z));
+
+        if progress.waypoint != Some(waypoint) {
+            // Reaching a waypoint on its own means the agent is moving again
+            if !progress.recovering {
+                progress.recovery_attempts = 0;
+            }
+            progress.recovering = false;
+            progress.waypoint = Some(waypoint);
+            progress.best_distance = distance;
+            progress.stall_time = 0.0;
+            continue;
+        }
+
+        if distance < progress.best_distance - MIN_PROGRESS {
+            progress.best_distance = distance;
+            progress.stall_time = 0.0;
+            continue;
+        }
+
+        progress.stall_time += time.delta_seconds();
+        if progress.stall_time < STALL_TIMEOUT {
+            continue;
+        }
+
+        progress.stall_time = 0.0;
+        progress.recovery_attempts += 1;
+        progress.recovering = true;
+
+        // First try skipping ahead to the furthest waypoint in sight
+        if progress.recovery_attempts == 1 {
+            if let Some(visible) = (1..path.len())
+                .rev()
+                .find(|&i| is_route_clear(&obstacle_polygons, position, &path[i..=i]))
+            {
+                path.drain(..visible);
+                if let Some(mut gizmo_path) = gizmo_path {
+                    gizmo_path.0 = Some(path.clone());
+                }
+                continue;
+            }
+            progress.recovery_attempts += 1;
+        }
+
+        // Then search for a fresh route to the same destination
+        if progress.recovery_attempts == 2 {
+            let goal = path[path.len() - 1];
+            if let Some(mut new_path) = find_path(
+                &nav_mesh,
+                &obstacle_polygons,
+                position,
+                goal,
+                &mut query_stats,
+            ) {
+                // The search starts at the agent's own position, which needs no visit
+                if new_path.len() > 1 && new_path[0].distance(position) < MIN_PROGRESS {
+                    new_path.remove(0);
+                }
+                *path = new_path;
+                if let Some(mut gizmo_path) = gizmo_path {
+                    gizmo_path.0 = Some(path.clone());
+                }
+                continue;
+            }
+        }
+
+        // Finally give up on the order altogether
+        target_position.0 = None;
+        if let Some(mut gizmo_path) = gizmo_path {
+            gizmo_path.0 = None;
+        }
+        if let Some(mut command_queue) = command_queue {
+            command_queue.clear();
+        }
+        progress.reset();
+        path_failed.send(PathFailed { entity });
+    }
+}
+
+pub fn report_failed_paths(mut path_failed: EventReader<PathFailed>) {
+    for event in path_failed.read() {
+        println!("{:?} is stuck and gave up its path.", event.entity);
+    }
+}
@@ -1,6 +1,17 @@
-use std::cmp::PartialEq;
+use bevy::math::bounding::Aabb2d;
+use bevy::math::{Vec2, Vec3};
+use std::cmp::{Ordering, PartialEq};
+use std::collections::HashMap;
+use std::fmt;
 use std::hash::{Hash, Hasher};
 
+/// Size of the grid points are snapped to for equality and hashing. Two points that
+/// round to the same grid cell are the same point, so a corner rebuilt from a `Vec3`
+/// still matches the polygon vertex it came from. Equality is cell identity rather
+/// than a tolerance: points a hair apart on either side of a cell boundary differ, so
+/// corners that should coincide are merged with `weld_vertices` first.
+pub const POINT_QUANTUM: f32 = 1e-4;
+
 #[derive(Debug, Clone)]
 pub struct Point {
     pub x: f32,
@@ -8,79 +19,869 @@ pub struct Point {
     pub z: f32,
 }
 
+impl Point {
+    /// The grid cell of `POINT_QUANTUM` that identifies this point.
+    pub fn key(&self) -> (i64, i64, i64) {
+        let snap = |value: f32| (value / POINT_QUANTUM).round() as i64;
+        (snap(self.x), snap(self.y), snap(self.z))
+    }
+}
+
 impl PartialEq for Point {
     fn eq(&self, other: &Self) -> bool {
-        self.x == other.x && self.y == other.y && self.z == other.z
+        self.key() == other.key()
     }
 }
 
 impl Eq for Point {}
 
+impl From<Vec3> for Point {
+    fn from(v: Vec3) -> Self {
+        Point {
+            x: v.x,
+            y: v.y,
+            z: v.z,
+        }
+    }
+}
+
+impl From<&Point> for Vec3 {
+    fn from(p: &Point) -> Self {
+        Vec3::new(p.x, p.y, p.z)
+    }
+}
+
 impl Hash for Point {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        // We can use the bit representation of the float for hashing
-        self.x.to_bits().hash(state);
-        self.y.to_bits().hash(state);
-        self.z.to_bits().hash(state);
+        // Hashing the grid cell keeps it consistent with equality
+        self.key().hash(state);
     }
 }
 
+/// Every point within `radius` of the segment `a`-`b` on the ground plane (x, z): a
+/// circle when the two ends coincide, otherwise a capsule or a buffered wall.
 #[derive(Debug, Clone)]
+pub struct Capsule {
+    pub a: Point,
+    pub b: Point,
+    pub radius: f32,
+}
+
+impl Capsule {
+    /// Whether the point lies strictly inside.
+    pub fn contains(&self, point: &Point) -> bool {
+        point_segment_distance(point, &self.a, &self.b) < self.radius
+    }
+
+    /// Whether the segment passes strictly inside; touching the outline is allowed, so
+    /// sight lines between the outline's corners stay open.
+    pub fn blocks(&self, line_start: &Point, line_end: &Point) -> bool {
+        segment_distance(line_start, line_end, &self.a, &self.b) < self.radius
+    }
+
+    /// A clockwise polygon with `segments` corners per full turn whose edges are
+    /// tangent to the capsule grown by `clearance`, so it encloses the capsule. The
+    /// straight sides of a capsule run between the corners of its two end caps.
+    pub fn outline(&self, segments: usize, clearance: f32) -> Polygon {
+        // An even count keeps the corners symmetric about the axis
+        let segments = segments.max(4) / 2 * 2;
+        let step = std::f32::consts::TAU / segments as f32;
+        let corner_radius = (self.radius + clearance) / (step / 2.0).cos();
+        let axis = Vec2::new(self.b.x - self.a.x, self.b.z - self.a.z);
+        let heading = if axis.length_squared() > 0.0 {
+            axis.y.atan2(axis.x)
+        } else {
+            0.0
+        };
+
+        // Corners sit half a step off the axis, so each lies clearly on one end cap
+        let mut outline = Polygon::new();
+        for i in 0..segments {
+            let angle = heading + (i as f32 + 0.5) * step;
+            let offset = Vec2::from_angle(angle) * corner_radius;
+            let end = if (angle - heading).cos() > 0.0 {
+                &self.b
+            } else {
+                &self.a
+            };
+            outline.add_vertex(end.x + offset.x, end.y, end.z + offset.y);
+        }
+        outline.set_clockwise(true);
+        outline
+    }
+}
+
+/// Corners per full turn of the outline round obstacles get; they are its nav vertices.
+pub const CAPSULE_SEGMENTS: usize = 8;
+/// How far the outline of a round obstacle stays clear of it, so that paths between its
+/// corners pass the exact line-of-sight test despite rounding.
+pub const CAPSULE_CLEARANCE: f32 = 0.01;
+
+#[derive(Debug, Clone, Default)]
 pub struct Polygon {
     pub vertices: Vec<Point>,
+    /// The exact shape of a round obstacle, which `vertices` only outline. Containment
+    /// and line of sight use it instead of the vertices when it is set.
+    pub capsule: Option<Capsule>,
 }
 
 impl Polygon {
     pub fn new() -> Self {
         Polygon {
             vertices: Vec::new(),
+            capsule: None,
+        }
+    }
+
+    /// A round obstacle: the exact capsule, outlined with corners for the nav mesh.
+    pub fn from_capsule(capsule: Capsule) -> Self {
+        Polygon {
+            vertices: capsule
+                .outline(CAPSULE_SEGMENTS, CAPSULE_CLEARANCE)
+                .vertices,
+            capsule: Some(capsule),
         }
     }
 
     pub fn add_vertex(&mut self, x: f32, y: f32, z: f32) {
         self.vertices.push(Point { x, y, z });
     }
+
+    /// Shoelace area on the ground plane (x, z). The sign tells the winding.
+    pub fn signed_area(&self) -> f32 {
+        let n = self.vertices.len();
+        let mut area = 0.0;
+        for i in 0..n {
+            let a = &self.vertices[i];
+            let b = &self.vertices[(i + 1) % n];
+            area += a.x * b.z - b.x * a.z;
+        }
+        area / 2.0
+    }
+
+    /// Whether the polygon encloses walkable space rather than blocking it. Obstacles
+    /// are wound clockwise (negative `signed_area`); a counterclockwise polygon is the
+    /// outer boundary of a walkable area and blocks everything outside it.
+    pub fn is_walkable_boundary(&self) -> bool {
+        self.signed_area() > 0.0
+    }
+
+    /// Reverses the vertex order if needed so the polygon winds the given way.
+    pub fn set_clockwise(&mut self, clockwise: bool) {
+        if (self.signed_area() < 0.0) != clockwise {
+            self.vertices.reverse();
+        }
+    }
+
+    /// Whether corner `i` sticks out into walkable space: a convex corner of an
+    /// obstacle or a reflex corner of a walkable boundary. Only such corners can be on
+    /// a shortest path; the others are hidden behind the polygon's own edges.
+    pub fn is_salient_corner(&self, i: usize) -> bool {
+        let n = self.vertices.len();
+        let previous = &self.vertices[(i + n - 1) % n];
+        let next = &self.vertices[(i + 1) % n];
+        // Both cases turn clockwise at the corner, whichever way the polygon winds
+        orientation(previous, &self.vertices[i], next) == Ordering::Less
+    }
+
+    /// Whether the bounding box of the segment overlaps that of the polygon.
+    fn bounds_overlap(&self, a: &Point, b: &Point) -> bool {
+        self.aabb().is_some_and(|aabb| {
+            a.x.max(b.x) >= aabb.min.x
+                && a.x.min(b.x) <= aabb.max.x
+                && a.z.max(b.z) >= aabb.min.y
+                && a.z.min(b.z) <= aabb.max.y
+        })
+    }
+
+    /// Whether no two edges cross or touch, apart from neighbours sharing their vertex.
+    pub fn is_simple(&self) -> bool {
+        self.self_intersections().is_empty()
+    }
+
+    /// Every pair of edges, by the index of their first vertex, that cross or touch
+    /// other than neighbours meeting in their shared vertex.
+    pub fn self_intersections(&self) -> Vec<(usize, usize)> {
+        let n = self.vertices.len();
+        let mut intersections = Vec::new();
+        for i in 0..n {
+            for j in i + 1..n {
+                // Neighbouring edges always meet in their shared vertex
+                if j == i + 1 || (i == 0 && j == n - 1) {
+                    continue;
+                }
+                if do_lines_intersect(
+                    &self.vertices[i],
+                    &self.vertices[(i + 1) % n],
+                    &self.vertices[j],
+                    &self.vertices[(j + 1) % n],
+                ) {
+                    intersections.push((i, j));
+                }
+            }
+        }
+        intersections
+    }
+
+    /// Moves every edge outwards by `radius`, or inwards for a negative radius, and
+    /// fills the gaps this opens at corners with `join`. Every join covers the rounded
+    /// offset, so the result keeps at least `radius` of clearance everywhere. Where
+    /// edges close in on each other they meet in a single vertex, and loops that fold
+    /// over themselves on concave polygons are cut off. The winding is kept.
+    pub fn offset(&self, radius: f32, join: Join) -> Polygon {
+        let n = self.vertices.len();
+        if n < 3 || radius == 0.0 {
+            return Polygon {
+                vertices: self.vertices.clone(),
+                capsule: None,
+            };
+        }
+
+        let clockwise = self.is_clockwise();
+        let distance = radius.abs();
+        // Unit normal of an edge towards the side the offset moves it to
+        let offset_normal = |a: &Point, b: &Point| {
+            let edge = Vec2::new(b.x - a.x, b.z - a.z).normalize_or_zero();
+            let outward = if clockwise {
+                Vec2::new(-edge.y, edge.x)
+            } else {
+                Vec2::new(edge.y, -edge.x)
+            };
+            outward * radius.signum()
+        };
+
+        let mut offset = Polygon::new();
+        for i in 0..n {
+            let previous = &self.vertices[(i + n - 1) % n];
+            let vertex = &self.vertices[i];
+            let next = &self.vertices[(i + 1) % n];
+            let corner = Vec2::new(vertex.x, vertex.z);
+            let incoming =
+                Vec2::new(vertex.x - previous.x, vertex.z - previous.z).normalize_or_zero();
+            let outgoing = Vec2::new(next.x - vertex.x, next.z - vertex.z).normalize_or_zero();
+            let n1 = offset_normal(previous, vertex);
+            let n2 = offset_normal(vertex, next);
+
+            // The offset edges part at a corner that turns away from the offset side
+            let turn = incoming.perp_dot(outgoing);
+            let side = incoming.perp_dot(n1);
+            let opens = turn * side < 0.0 || (turn == 0.0 && incoming.dot(outgoing) < 0.0);
+            let mitre_length = 1.0 / ((1.0 + n1.dot(n2)) / 2.0).max(f32::EPSILON).sqrt();
+
+            let corners: Vec<Vec2> = match join {
+                _ if !opens => {
+                    // The offset edges cross, or carry straight on, at the mitre point
+                    vec![corner + (n1 + n2) * distance / (1.0 + n1.dot(n2)).max(f32::EPSILON)]
+                }
+                Join::Mitre { limit } if mitre_length <= limit.max(1.0) => {
+                    vec![corner + (n1 + n2) * distance / (1.0 + n1.dot(n2))]
+                }
+                Join::Mitre { limit } => square_corner(
+                    corner,
+                    incoming,
+                    outgoing,
+                    (n1, n2),
+                    distance,
+                    distance * limit.max(1.0),
+                ),
+                Join::Square => {
+                    square_corner(corner, incoming, outgoing, (n1, n2), distance, distance)
+                }
+                Join::Round => {
+                    // Corners circumscribe the arc, so its edges are tangent to it
+                    let mut sweep = n1.angle_between(n2);
+                    // The arc goes round the corner, which matters for a hairpin
+                    if Vec2::from_angle(sweep / 2.0).rotate(n1).dot(incoming) < 0.0 {
+                        sweep -= std::f32::consts::TAU.copysign(sweep);
+                    }
+                    let steps = (sweep.abs() / ROUND_JOIN_STEP).ceil().max(1.0);
+                    let step = sweep / steps;
+                    let corner_distance = distance / (step / 2.0).cos();
+                    let mut corners = vec![corner + n1 * distance];
+                    corners.extend((0..steps as usize).map(|k| {
+                        corner
+                            + Vec2::from_angle(step * (k as f32 + 0.5)).rotate(n1) * corner_distance
+                    }));
+                    corners.push(corner + n2 * distance);
+                    corners
+                }
+            };
+            for point in corners {
+                offset.add_vertex(point.x, vertex.y, point.y);
+            }
+        }
+
+        let offset = offset.cleaned();
+        if offset.vertices.len() < 3 || offset.is_simple() {
+            return offset;
+        }
+        match offset.repaired() {
+            Ok(mut repaired) => {
+                repaired.set_clockwise(clockwise);
+                repaired
+            }
+            Err(_) => offset,
+        }
+    }
+
+    /// Splits a simple polygon into triangles by ear clipping. The triangles index into
+    /// `vertices` and keep the polygon's winding.
+    pub fn triangulate(&self) -> Vec<[usize; 3]> {
+        let winding = self.signed_area().signum() as f64;
+        let mut remaining: Vec<usize> = (0..self.vertices.len()).collect();
+        let mut triangles = Vec::new();
+
+        while remaining.len() > 3 {
+            let count = remaining.len();
+            let ear = (0..count).find(|&i| {
+                let a = &self.vertices[remaining[(i + count - 1) % count]];
+                let b = &self.vertices[remaining[i]];
+                let c = &self.vertices[remaining[(i + 1) % count]];
+                if direction(a, b, c) * winding <= 0.0 {
+                    return false;
+                }
+                // No other vertex may lie inside the ear
+                remaining.iter().all(|&other| {
+                    let p = &self.vertices[other];
+                    p == a
+                        || p == b
+                        || p == c
+                        || direction(a, b, p) * winding < 0.0
+                        || direction(b, c, p) * winding < 0.0
+                        || direction(c, a, p) * winding < 0.0
+                })
+            });
+
+            // Degenerate leftovers (collinear runs) have no ear; drop the first vertex
+            let i = ear.unwrap_or(0);
+            let previous = remaining[(i + count - 1) % count];
+            let next = remaining[(i + 1) % count];
+            if ear.is_some() {
+                triangles.push([previous, remaining[i], next]);
+            }
+            remaining.remove(i);
+        }
+
+        if remaining.len() == 3 {
+            triangles.push([remaining[0], remaining[1], remaining[2]]);
+        }
+        triangles
+    }
+
+    /// Unsigned area on the ground plane.
+    pub fn area(&self) -> f32 {
+        self.signed_area().abs()
+    }
+
+    /// Whether the polygon winds clockwise (negative `signed_area`), as obstacles do.
+    /// A polygon without area winds neither way.
+    pub fn is_clockwise(&self) -> bool {
+        self.signed_area() < 0.0
+    }
+
+    /// Area centroid on the ground plane, at the height of the first vertex. `None` for
+    /// a polygon without area, whose centroid is undefined.
+    pub fn centroid(&self) -> Option<Point> {
+        let n = self.vertices.len();
+        let area = self.signed_area();
+        if n < 3 || area == 0.0 {
+            return None;
+        }
+        let (mut x, mut z) = (0.0, 0.0);
+        for i in 0..n {
+            let a = &self.vertices[i];
+            let b = &self.vertices[(i + 1) % n];
+            let cross = a.x * b.z - b.x * a.z;
+            x += (a.x + b.x) * cross;
+            z += (a.z + b.z) * cross;
+        }
+        Some(Point {
+            x: x / (6.0 * area),
+            y: self.vertices[0].y,
+            z: z / (6.0 * area),
+        })
+    }
+
+    /// Bounding box on the ground plane, with z as the second axis. `None` when there
+    /// are no vertices.
+    pub fn aabb(&self) -> Option<Aabb2d> {
+        let first = self.vertices.first()?;
+        let mut min = Vec2::new(first.x, first.z);
+        let mut max = min;
+        for vertex in &self.vertices[1..] {
+            min = min.min(Vec2::new(vertex.x, vertex.z));
+            max = max.max(Vec2::new(vertex.x, vertex.z));
+        }
+        Some(Aabb2d { min, max })
+    }
+
+    /// The convex hull of the vertices; see `convex_hull`.
+    pub fn convex_hull(&self) -> Polygon {
+        convex_hull(&self.vertices)
+    }
+
+    /// The polygon without repeated vertices and without vertices exactly in line with
+    /// their neighbours, spikes included. It may end up with fewer than three vertices.
+    pub fn cleaned(&self) -> Polygon {
+        let mut vertices = self.vertices.clone();
+        vertices.dedup();
+        while vertices.len() > 1 && vertices.first() == vertices.last() {
+            vertices.pop();
+        }
+
+        let mut i = 0;
+        while vertices.len() >= 3 && i < vertices.len() {
+            let n = vertices.len();
+            let previous = &vertices[(i + n - 1) % n];
+            let next = &vertices[(i + 1) % n];
+            if orientation(previous, &vertices[i], next) == Ordering::Equal {
+                vertices.remove(i);
+                // The previous vertex may now be in line with its new neighbour
+                i = i.saturating_sub(1);
+            } else {
+                i += 1;
+            }
+        }
+
+        Polygon {
+            vertices,
+            capsule: None,
+        }
+    }
+
+    /// Douglas-Peucker simplification of the closed outline: vertices that lie within
+    /// `tolerance` of the simplified outline are dropped. The outline is split at the
+    /// first vertex and the one farthest from it, which are always kept. A polygon that
+    /// would be left with fewer than three vertices is returned unchanged.
+    pub fn simplify_douglas_peucker(&self, tolerance: f32) -> Polygon {
+        let n = self.vertices.len();
+        if n <= 3 {
+            return self.clone();
+        }
+
+        let first = &self.vertices[0];
+        let far = (1..n)
+            .max_by(|&a, &b| {
+                let distance = |i: usize| {
+                    Vec2::new(self.vertices[i].x - first.x, self.vertices[i].z - first.z)
+                        .length_squared()
+                };
+                distance(a).total_cmp(&distance(b))
+            })
+            .unwrap_or(1);
+
+        let mut keep = vec![false; n + 1];
+        keep[0] = true;
+        keep[far] = true;
+        keep[n] = true;
+        let vertex = |i: usize| &self.vertices[i % n];
+        let mut stack = vec![(0, far), (far, n)];
+        while let Some((start, end)) = stack.pop() {
+            let farthest = (start + 1..end)
+                .map(|i| {
+                    (
+                        i,
+                        point_segment_distance(vertex(i), vertex(start), vertex(end)),
+                    )
+                })
+                .max_by(|a, b| a.1.total_cmp(&b.1));
+            if let Some((i, distance)) = farthest {
+                if distance > tolerance {
+                    keep[i] = true;
+                    stack.push((start, i));
+                    stack.push((i, end));
+                }
+            }
+        }
+
+        let vertices: Vec<Point> = (0..n)
+            .filter(|&i| keep[i])
+            .map(|i| self.vertices[i].clone())
+            .collect();
+        if vertices.len() < 3 {
+            return self.clone();
+        }
+        Polygon {
+            vertices,
+            capsule: None,
+        }
+    }
+
+    /// Visvalingam-Whyatt simplification: repeatedly drops the vertex whose triangle
+    /// with its neighbours has the smallest area, while that area is below `min_area`.
+    /// It stops at a triangle.
+    pub fn simplify_visvalingam(&self, min_area: f32) -> Polygon {
+        let mut vertices = self.vertices.clone();
+        while vertices.len() > 3 {
+            let n = vertices.len();
+            let effective_area = |i: usize| {
+                let a = &vertices[(i + n - 1) % n];
+                let b = &vertices[i];
+                let c = &vertices[(i + 1) % n];
+                (direction(a, b, c) / 2.0).abs() as f32
+            };
+            let Some((smallest, area)) = (0..n)
+                .map(|i| (i, effective_area(i)))
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+            else {
+                break;
+            };
+            if area >= min_area {
+                break;
+            }
+            vertices.remove(smallest);
+        }
+        Polygon {
+            vertices,
+            capsule: None,
+        }
+    }
+
+    /// Checks that the polygon can be an obstacle footprint: after `cleaned`, at least
+    /// three vertices, some area and no crossing edges. The result winds clockwise.
+    pub fn validated(&self) -> Result<Polygon, PolygonError> {
+        let mut polygon = self.cleaned();
+        if polygon.vertices.len() < 3 {
+            return Err(PolygonError::TooFewVertices);
+        }
+        if polygon.signed_area() == 0.0 {
+            return Err(PolygonError::NoArea);
+        }
+        if !polygon.is_simple() {
+            return Err(PolygonError::SelfIntersecting);
+        }
+        polygon.set_clockwise(true);
+        Ok(polygon)
+    }
+
+    /// `validated`, but a polygon whose edges cross is first untangled: it is split at
+    /// a crossing into two loops and the larger one is kept, until no crossing is left.
+    /// Overlapping collinear edges, which have no single crossing point, fall back to
+    /// the convex hull.
+    pub fn repaired(&self) -> Result<Polygon, PolygonError> {
+        let mut polygon = self.cleaned();
+        // Every split removes at least one vertex from the loop that is kept
+        for _ in 0..self.vertices.len() {
+            let Some(&(i, j)) = polygon.self_intersections().first() else {
+                break;
+            };
+            polygon = polygon.split_at_crossing(i, j).cleaned();
+        }
+        match polygon.validated() {
+            Err(PolygonError::SelfIntersecting) => polygon.convex_hull().validated(),
+            result => result,
+        }
+    }
+
+    /// Splits the outline where edges `i` and `j` meet, keeping the larger loop.
+    fn split_at_crossing(&self, i: usize, j: usize) -> Polygon {
+        let n = self.vertices.len();
+        let (a1, a2) = (&self.vertices[i], &self.vertices[(i + 1) % n]);
+        let (b1, b2) = (&self.vertices[j], &self.vertices[(j + 1) % n]);
+        let Some(t) = segment_intersection(a1, a2, b1, b2) else {
+            return self.convex_hull();
+        };
+        let crossing = Point {
+            x: a1.x + (a2.x - a1.x) * t,
+            y: a1.y,
+            z: a1.z + (a2.z - a1.z) * t,
+        };
+
+        let mut inner = Polygon::new();
+        inner.vertices.push(crossing.clone());
+        inner
+            .vertices
+            .extend(self.vertices[i + 1..=j].iter().cloned());
+        let mut outer = Polygon::new();
+        outer.vertices.push(crossing);
+        outer.vertices.extend(
+            self.vertices[j + 1..]
+                .iter()
+                .chain(&self.vertices[..=i])
+                .cloned(),
+        );
+
+        if inner.area() > outer.area() {
+            inner
+        } else {
+            outer
+        }
+    }
+}
+
+/// Why a polygon cannot be used as an obstacle footprint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolygonError {
+    TooFewVertices,
+    NoArea,
+    SelfIntersecting,
+}
+
+impl fmt::Display for PolygonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PolygonError::TooFewVertices => write!(f, "it needs at least three points"),
+            PolygonError::NoArea => write!(f, "it has no area"),
+            PolygonError::SelfIntersecting => write!(f, "its edges cross"),
+        }
+    }
+}
+
+impl std::error::Error for PolygonError {}
+
+/// How the offset edges of a polygon are joined where they part at a corner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Join {
+    /// An arc around the corner, drawn with a corner every `ROUND_JOIN_STEP`.
+    Round,
+    /// The edges extended until they meet, cut off square once the mitre sticks out
+    /// more than `limit` times the offset from the corner.
+    Mitre { limit: f32 },
+    /// The edges cut off square, tangent to the arc of a round join.
+    Square,
+}
+
+impl Default for Join {
+    fn default() -> Self {
+        Join::Mitre { limit: MITRE_LIMIT }
+    }
+}
+
+/// How many times the offset distance a mitred corner may stick out by default.
+pub const MITRE_LIMIT: f32 = 2.0;
+/// Largest angle a single edge of a round join turns through.
+const ROUND_JOIN_STEP: f32 = std::f32::consts::FRAC_PI_4;
+
+/// The two corners of a join cut off square, `cut` from the polygon corner along the
+/// bisector of the offset normals: where the offset edges cross the cut line.
+fn square_corner(
+    corner: Vec2,
+    incoming: Vec2,
+    outgoing: Vec2,
+    (n1, n2): (Vec2, Vec2),
+    distance: f32,
+    cut: f32,
+) -> Vec<Vec2> {
+    // A hairpin has opposite normals; its cap is cut beyond the tip
+    let bisector = (n1 + n2).try_normalize().unwrap_or(incoming);
+    let along = |edge: Vec2, normal: Vec2| {
+        let offset_corner = corner + normal * distance;
+        offset_corner + edge * (cut - distance * normal.dot(bisector)) / edge.dot(bisector)
+    };
+    vec![along(incoming, n1), along(outgoing, n2)]
+}
+
+/// Merges vertices of the polygons that lie within `distance` of each other on the
+/// ground plane, so that corners shared by adjacent polygons become the same point.
+/// Every vertex moves onto the exact coordinates of the first vertex of its cluster,
+/// so welded corners compare equal whichever `POINT_QUANTUM` cells they started in.
+/// Each welded polygon is `validated` again, keeping its winding. Polygons that
+/// collapse to fewer than three vertices or no area are dropped, and those whose edges
+/// would cross keep their unwelded outline. Returns how many polygons were dropped.
+pub fn weld_vertices(polygons: &mut Vec<Polygon>, distance: f32) -> usize {
+    if distance <= 0.0 {
+        return 0;
+    }
+
+    let cell_of = |point: &Point| {
+        (
+            (point.x / distance).floor() as i64,
+            (point.z / distance).floor() as i64,
+        )
+    };
+    let mut welded: Vec<Point> = Vec::new();
+    let mut grid: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+
+    let count = polygons.len();
+    polygons.retain_mut(|polygon| {
+        // Round obstacles keep their outline, which must stay clear of the exact shape
+        if polygon.capsule.is_some() {
+            return true;
+        }
+
+        let original = polygon.clone();
+        for vertex in polygon.vertices.iter_mut() {
+            let (cell_x, cell_z) = cell_of(vertex);
+            let existing = (-1..=1)
+                .flat_map(|dx| (-1..=1).map(move |dz| (cell_x + dx, cell_z + dz)))
+                .filter_map(|cell| grid.get(&cell))
+                .flatten()
+                .copied()
+                .find(|&index| {
+                    let other = &welded[index];
+                    (other.x - vertex.x).hypot(other.z - vertex.z) <= distance
+                });
+
+            match existing {
+                Some(index) => *vertex = welded[index].clone(),
+                None => {
+                    grid.entry((cell_x, cell_z)).or_default().push(welded.len());
+                    welded.push(vertex.clone());
+                }
+            }
+        }
+
+        match polygon.validated() {
+            Ok(mut valid) => {
+                valid.set_clockwise(!original.is_walkable_boundary());
+                *polygon = valid;
+                true
+            }
+            Err(PolygonError::SelfIntersecting) => {
+                *polygon = original;
+                true
+            }
+            Err(_) => false,
+        }
+    });
+    count - polygons.len()
 }
 
 pub fn do_lines_intersect(p1: &Point, p2: &Point, q1: &Point, q2: &Point) -> bool {
-    let d1 = direction(q1, q2, p1);
-    let d2 = direction(q1, q2, p2);
-    let d3 = direction(p1, p2, q1);
-    let d4 = direction(p1, p2, q2);
+    let d1 = orientation(q1, q2, p1);
+    let d2 = orientation(q1, q2, p2);
+    let d3 = orientation(p1, p2, q1);
+    let d4 = orientation(p1, p2, q2);
 
-    if d1 * d2 < 0.0 && d3 * d4 < 0.0 {
+    // Proper crossing: each segment has the other's ends strictly on opposite sides
+    if d1 != Ordering::Equal
+        && d2 != Ordering::Equal
+        && d1 != d2
+        && d3 != Ordering::Equal
+        && d4 != Ordering::Equal
+        && d3 != d4
+    {
         return true;
     }
 
-    if d1 == 0.0 && on_segment(q1, q2, p1) {
+    if d1 == Ordering::Equal && on_segment(q1, q2, p1) {
         return true;
     }
-    if d2 == 0.0 && on_segment(q1, q2, p2) {
+    if d2 == Ordering::Equal && on_segment(q1, q2, p2) {
         return true;
     }
-    if d3 == 0.0 && on_segment(p1, p2, q1) {
+    if d3 == Ordering::Equal && on_segment(p1, p2, q1) {
         return true;
     }
-    if d4 == 0.0 && on_segment(p1, p2, q2) {
+    if d4 == Ordering::Equal && on_segment(p1, p2, q2) {
         return true;
     }
 
     false
 }
 
-pub fn direction(p: &Point, q: &Point, r: &Point) -> f32 {
-    (q.x - p.x) * (r.z - p.z) - (q.z - p.z) * (r.x - p.x)
+/// Returns how far along `p1`-`p2` the segment `q1`-`q2` is crossed, as a fraction of
+/// its length, or `None` when the segments are parallel or do not meet.
+pub fn segment_intersection(p1: &Point, p2: &Point, q1: &Point, q2: &Point) -> Option<f32> {
+    let r = (p2.x - p1.x, p2.z - p1.z);
+    let s = (q2.x - q1.x, q2.z - q1.z);
+    let denominator = r.0 * s.1 - r.1 * s.0;
+    if denominator.abs() < f32::EPSILON {
+        return None;
+    }
+
+    let offset = (q1.x - p1.x, q1.z - p1.z);
+    let t = (offset.0 * s.1 - offset.1 * s.0) / denominator;
+    let u = (offset.0 * r.1 - offset.1 * r.0) / denominator;
+
+    ((0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u)).then_some(t)
 }
 
+/// Twice the signed area of the triangle `p`, `q`, `r` on the ground plane (x, z), with
+/// the same sign convention as `Polygon::signed_area`. The sign is exact: it comes from
+/// `orient2d`.
+pub fn direction(p: &Point, q: &Point, r: &Point) -> f64 {
+    orient2d((p.x, p.z), (q.x, q.z), (r.x, r.z))
+}
+
+/// Which side of the line through `p` and `q` the point `r` is on: `Greater` and `Less`
+/// for the two sides, `Equal` when the three points are exactly collinear.
+pub fn orientation(p: &Point, q: &Point, r: &Point) -> Ordering {
+    direction(p, q, r)
+        .partial_cmp(&0.0)
+        .unwrap_or(Ordering::Equal)
+}
+
+/// Whether `r` lies within the bounding box of `p`-`q`. For a point already known to be
+/// collinear with the segment, that is whether it lies on it.
 pub fn on_segment(p: &Point, q: &Point, r: &Point) -> bool {
     r.x >= p.x.min(q.x) && r.x <= p.x.max(q.x) && r.z >= p.z.min(q.z) && r.z <= p.z.max(q.z)
 }
 
+/// Relative error bound of the plain `f64` evaluation in `orient2d`, after Shewchuk's
+/// "Adaptive Precision Floating-Point Arithmetic and Fast Robust Geometric Predicates".
+const ORIENT2D_ERROR_BOUND: f64 = (3.0 + 16.0 * HALF_EPSILON) * HALF_EPSILON;
+const HALF_EPSILON: f64 = f64::EPSILON / 2.0;
+
+/// The orientation determinant of `a`, `b`, `c` with an exact sign. The `f64` result is
+/// used when it is clear of its error bound; otherwise the determinant is summed
+/// exactly. That is possible because the products of two `f32` coordinates are exact in
+/// `f64`.
+pub fn orient2d(a: (f32, f32), b: (f32, f32), c: (f32, f32)) -> f64 {
+    let (ax, ay) = (a.0 as f64, a.1 as f64);
+    let (bx, by) = (b.0 as f64, b.1 as f64);
+    let (cx, cy) = (c.0 as f64, c.1 as f64);
+
+    let left = (ax - cx) * (by - cy);
+    let right = (ay - cy) * (bx - cx);
+    let determinant = left - right;
+
+    let bound = ORIENT2D_ERROR_BOUND * (left.abs() + right.abs());
+    if determinant.abs() > bound {
+        return determinant;
+    }
+
+    // The determinant expanded into six exact products, summed without rounding
+    let mut expansion = Vec::with_capacity(12);
+    for term in [
+        ax * by,
+        -(ax * cy),
+        -(cx * by),
+        -(ay * bx),
+        ay * cx,
+        cy * bx,
+    ] {
+        grow_expansion(&mut expansion, term);
+    }
+
+    // The components do not overlap and grow in magnitude, so the last one has the sign
+    expansion.last().copied().unwrap_or(0.0)
+}
+
+/// Error-free addition: `a + b == sum + error` exactly.
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let sum = a + b;
+    let b_virtual = sum - a;
+    let a_virtual = sum - b_virtual;
+    let error = (a - a_virtual) + (b - b_virtual);
+    (sum, error)
+}
+
+/// Adds `value` to a nonoverlapping expansion, dropping zero components.
+fn grow_expansion(expansion: &mut Vec<f64>, value: f64) {
+    let mut carry = value;
+    let mut grown = Vec::with_capacity(expansion.len() + 1);
+    for &component in expansion.iter() {
+        let (sum, error) = two_sum(carry, component);
+        if error != 0.0 {
+            grown.push(error);
+        }
+        carry = sum;
+    }
+    if carry != 0.0 {
+        grown.push(carry);
+    }
+    *expansion = grown;
+}
+
 pub fn does_line_intersect_polygon(
     line_start: &Point,
     line_end: &Point,
     polygon: &Polygon,
 ) -> bool {
+    if let Some(capsule) = &polygon.capsule {
+        return capsule.blocks(line_start, line_end);
+    }
     let n = polygon.vertices.len();
     for i in 0..n {
         let next_i = (i + 1) % n;
@@ -96,94 +897,587 @@ pub fn does_line_intersect_polygon(
     false
 }
 
+/// The convex hull of the points on the ground plane (x, z), wound clockwise like the
+/// obstacle polygons, by Andrew's monotone chain. Collinear points are left out, so
+/// fewer than three points come back when all of them lie on a line.
+pub fn convex_hull(points: &[Point]) -> Polygon {
+    let mut sorted: Vec<&Point> = points.iter().collect();
+    sorted.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.z.total_cmp(&b.z)));
+    sorted.dedup_by(|a, b| a.x == b.x && a.z == b.z);
+    if sorted.len() < 3 {
+        let mut hull = Polygon::new();
+        hull.vertices = sorted.into_iter().cloned().collect();
+        return hull;
+    }
+
+    // Lower and upper chains, each only turning counterclockwise
+    let mut chain: Vec<&Point> = Vec::with_capacity(sorted.len() * 2);
+    for pass in [sorted.clone(), sorted.iter().rev().copied().collect()] {
+        let base = chain.len();
+        for point in pass {
+            while chain.len() >= base + 2
+                && orientation(chain[chain.len() - 2], chain[chain.len() - 1], point)
+                    != Ordering::Greater
+            {
+                chain.pop();
+            }
+            chain.push(point);
+        }
+        // The last point of each chain starts the other one
+        chain.pop();
+    }
+
+    let mut hull = Polygon::new();
+    hull.vertices = chain.into_iter().cloned().collect();
+    hull.set_clockwise(true);
+    hull
+}
+
+/// Distance on the ground plane (x, z) from `point` to the segment `a`-`b`.
+pub fn point_segment_distance(point: &Point, a: &Point, b: &Point) -> f32 {
+    let segment = Vec2::new(b.x - a.x, b.z - a.z);
+    let offset = Vec2::new(point.x - a.x, point.z - a.z);
+    let length_squared = segment.length_squared();
+    let t = if length_squared > 0.0 {
+        (offset.dot(segment) / length_squared).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (offset - segment * t).length()
+}
+
+/// Shortest distance on the ground plane (x, z) between the segments `p1`-`p2` and
+/// `q1`-`q2`, zero when they touch or cross.
+pub fn segment_distance(p1: &Point, p2: &Point, q1: &Point, q2: &Point) -> f32 {
+    if do_lines_intersect(p1, p2, q1, q2) {
+        return 0.0;
+    }
+    point_segment_distance(p1, q1, q2)
+        .min(point_segment_distance(p2, q1, q2))
+        .min(point_segment_distance(q1, p1, p2))
+        .min(point_segment_distance(q2, p1, p2))
+}
+
+/// Crossing-number test on the ground plane (x, z). Points exactly on the boundary may
+/// land on either side. Round obstacles are tested against their exact capsule.
+pub fn point_in_polygon(point: &Point, polygon: &Polygon) -> bool {
+    if let Some(capsule) = &polygon.capsule {
+        return capsule.contains(point);
+    }
+    let n = polygon.vertices.len();
+    let mut inside = false;
+    for i in 0..n {
+        let a = &polygon.vertices[i];
+        let b = &polygon.vertices[(i + 1) % n];
+        if (a.z > point.z) != (b.z > point.z) {
+            let x_at_z = a.x + (point.z - a.z) / (b.z - a.z) * (b.x - a.x);
+            if point.x < x_at_z {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+/// Whether the segment passes through the blocked side of the polygon: the inside of an
+/// obstacle or the outside of a walkable boundary. Touching the boundary, running along
+/// an edge or passing through a corner does not count, so concave polygons keep the
+/// sight lines between their corners that stay in free space.
 pub fn line_intersects_polygon_with_vertex_check(
     line_start: &Point,
     line_end: &Point,
     polygon: &Polygon,
 ) -> bool {
+    if let Some(capsule) = &polygon.capsule {
+        return capsule.blocks(line_start, line_end);
+    }
     let n = polygon.vertices.len();
-    let mut line_start_on_edge = false;
-    let mut line_start_edge_index = None;
-    let mut line_start_index = None;
-    let mut line_end_index = None;
+    if n < 3 {
+        return false;
+    }
+    let boundary = polygon.is_walkable_boundary();
+    if !boundary && !polygon.bounds_overlap(line_start, line_end) {
+        return false;
+    }
+
+    // A proper crossing of any edge leaves or enters the blocked side
+    for i in 0..n {
+        let v1 = &polygon.vertices[i];
+        let v2 = &polygon.vertices[(i + 1) % n];
+        let d1 = orientation(v1, v2, line_start);
+        let d2 = orientation(v1, v2, line_end);
+        let d3 = orientation(line_start, line_end, v1);
+        let d4 = orientation(line_start, line_end, v2);
+        if d1 != Ordering::Equal
+            && d2 != Ordering::Equal
+            && d1 != d2
+            && d3 != Ordering::Equal
+            && d4 != Ordering::Equal
+            && d3 != d4
+        {
+            return true;
+        }
+    }
+
+    // Otherwise the segment only meets the boundary at the points below, and each piece
+    // between two of them lies entirely on one side
+    let dx = line_end.x - line_start.x;
+    let dz = line_end.z - line_start.z;
+    let length_squared = dx * dx + dz * dz;
+    if length_squared == 0.0 {
+        return point_in_polygon(line_start, polygon) != boundary;
+    }
 
-    // Determine if line_start and line_end are vertices of the polygon
+    // Each contact is a position along the segment and the edges it lies on
+    let edges_through = |point: &Point| -> Vec<usize> {
+        (0..n)
+            .filter(|&i| {
+                let v1 = &polygon.vertices[i];
+                let v2 = &polygon.vertices[(i + 1) % n];
+                orientation(v1, v2, point) == Ordering::Equal && on_segment(v1, v2, point)
+            })
+            .collect()
+    };
+    let mut contacts = vec![
+        (0.0, edges_through(line_start)),
+        (1.0, edges_through(line_end)),
+    ];
     for (i, vertex) in polygon.vertices.iter().enumerate() {
-        if line_start == vertex {
-            line_start_index = Some(i);
+        if vertex == line_start || vertex == line_end {
+            continue;
         }
-        if line_end == vertex {
-            line_end_index = Some(i);
+        if orientation(line_start, line_end, vertex) == Ordering::Equal
+            && on_segment(line_start, line_end, vertex)
+        {
+            let t =
+                ((vertex.x - line_start.x) * dx + (vertex.z - line_start.z) * dz) / length_squared;
+            contacts.push((t, vec![(i + n - 1) % n, i]));
         }
     }
+    contacts.sort_by(|a, b| a.0.total_cmp(&b.0));
 
-    // Check if both points are vertices and are not neighbors
-    if let (Some(start_idx), Some(end_idx)) = (line_start_index, line_end_index) {
-        // Calculate if they are neighbors (i.e., consecutive in the vertex list)
-        if (start_idx + 1) % n != end_idx && start_idx != (end_idx + 1) % n {
-            return true;
+    contacts.windows(2).any(|pair| {
+        let (t1, edges1) = &pair[0];
+        let (t2, edges2) = &pair[1];
+        // A piece between two points of the same edge runs along that edge
+        if t2 <= t1 || edges1.iter().any(|edge| edges2.contains(edge)) {
+            return false;
+        }
+        let t = (t1 + t2) / 2.0;
+        let midpoint = Point {
+            x: line_start.x + dx * t,
+            y: line_start.y,
+            z: line_start.z + dz * t,
+        };
+        point_in_polygon(&midpoint, polygon) != boundary
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A polygon from (x, z) corners, in the order given.
+    fn polygon(corners: &[(f32, f32)]) -> Polygon {
+        let mut polygon = Polygon::new();
+        for &(x, z) in corners {
+            polygon.add_vertex(x, 0.0, z);
         }
+        polygon
     }
 
-    // Check if line_start is on an edge of the polygon (but not on a vertex)
-    for i in 0..n {
-        let next_i = (i + 1) % n;
-        let v1 = &polygon.vertices[i];
-        let v2 = &polygon.vertices[next_i];
+    /// Distance from the point to the nearest edge of the polygon.
+    fn distance_to_outline(point: &Point, polygon: &Polygon) -> f32 {
+        let n = polygon.vertices.len();
+        (0..n)
+            .map(|i| {
+                point_segment_distance(point, &polygon.vertices[i], &polygon.vertices[(i + 1) % n])
+            })
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    #[test]
+    fn welding_drops_collapsed_polygons_and_keeps_winding() {
+        let mut square = polygon(&[(0.0, 0.0), (0.0, 2.0), (2.0, 2.0), (2.0, 0.0)]);
+        square.set_clockwise(true);
+        // Every corner of the sliver is within the weld distance of the square's corner
+        let sliver = polygon(&[(2.05, 0.0), (2.1, 0.05), (2.1, -0.05)]);
+        let mut boundary = polygon(&[(-5.0, -5.0), (5.0, -5.0), (5.0, 5.0), (-5.0, 5.0)]);
+        boundary.set_clockwise(false);
+
+        let mut polygons = vec![square, sliver, boundary];
+        assert_eq!(weld_vertices(&mut polygons, 0.2), 1);
+        assert_eq!(polygons.len(), 2);
+        assert!(polygons[0].is_clockwise());
+        assert_eq!(polygons[0].vertices.len(), 4);
+        assert!(polygons[1].is_walkable_boundary());
+    }
+
+    #[test]
+    fn welding_merges_points_across_a_quantum_boundary() {
+        // A millionth apart, but rounding into neighbouring grid cells
+        let left_x = 0.5 * POINT_QUANTUM - 1e-6;
+        let right_x = 0.5 * POINT_QUANTUM + 1e-6;
+        let corner = |x: f32| Point { x, y: 0.0, z: 0.0 };
+        assert_ne!(corner(left_x), corner(right_x));
+
+        let mut left = polygon(&[(-2.0, -1.0), (left_x, 0.0), (-2.0, 1.0)]);
+        left.set_clockwise(true);
+        let mut right = polygon(&[(right_x, 0.0), (2.0, -1.0), (2.0, 1.0)]);
+        right.set_clockwise(true);
+
+        let mut polygons = vec![left, right];
+        assert_eq!(weld_vertices(&mut polygons, 0.01), 0);
+        let shared = |polygon: &Polygon| {
+            polygon
+                .vertices
+                .iter()
+                .find(|vertex| vertex.x.abs() < 1.0)
+                .cloned()
+                .unwrap()
+        };
+        let (left_corner, right_corner) = (shared(&polygons[0]), shared(&polygons[1]));
+        assert_eq!(left_corner, right_corner);
+        assert_eq!(left_corner.x, right_corner.x);
+    }
+
+    #[test]
+    fn offset_moves_square_edges_by_the_radius() {
+        let mut square = polygon(&[(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)]);
+        square.set_clockwise(true);
 
-        if on_segment(v1, v2, line_start) && line_start != v1 && line_start != v2 {
-            line_start_on_edge = true;
-            line_start_edge_index = Some(i);
-            break;
+        let grown = square.offset(1.0, Join::Mitre { limit: 2.0 });
+        assert!(grown.is_clockwise());
+        assert_eq!(grown.area(), 16.0);
+        let shrunk = square.offset(-0.5, Join::default());
+        assert!(shrunk.is_clockwise());
+        assert_eq!(shrunk.area(), 1.0);
+    }
+
+    #[test]
+    fn offset_caps_a_hairpin() {
+        // A slit sticks out of the square to (4, 1) and doubles straight back
+        let slit = polygon(&[
+            (0.0, 0.0),
+            (0.0, 2.0),
+            (2.0, 2.0),
+            (2.0, 1.0),
+            (4.0, 1.0),
+            (2.0, 1.0),
+            (2.0, 0.0),
+        ]);
+        assert!(slit.is_clockwise());
+
+        for join in [Join::default(), Join::Square, Join::Round] {
+            let offset = slit.offset(0.5, join);
+            assert!(offset.is_simple(), "{join:?}");
+            assert!(offset.is_clockwise(), "{join:?}");
+            let tip = offset
+                .vertices
+                .iter()
+                .map(|vertex| vertex.x)
+                .fold(0.0, f32::max);
+            assert!(tip >= 4.5 - 1e-4, "{join:?}");
+            for vertex in &offset.vertices {
+                assert!(distance_to_outline(vertex, &slit) >= 0.5 - 1e-4, "{join:?}");
+            }
         }
     }
 
-    if line_start_on_edge {
-        // Check if line_end is on a vertex of the polygon
-        for (i, vertex) in polygon.vertices.iter().enumerate() {
-            if line_end == vertex {
-                // If line_end is on a vertex, check if it's a vertex of the edge line_start is on
-                if let Some(start_edge_index) = line_start_edge_index {
-                    let next_index = (start_edge_index + 1) % n;
-                    if i == start_edge_index || i == next_index {
-                        return false; // line_start and line_end are on the same edge
-                    }
-                }
-                // Otherwise, continue to intersection check
+    #[test]
+    fn offset_keeps_the_clearance_at_sharp_corners() {
+        // A clockwise spike whose tip is far past any mitre limit
+        let spike = polygon(&[(0.0, 0.0), (10.0, 0.5), (10.0, -0.5)]);
+        assert!(spike.is_clockwise());
+
+        for join in [Join::default(), Join::Square, Join::Round] {
+            let offset = spike.offset(0.5, join);
+            for vertex in &offset.vertices {
+                assert!(!point_in_polygon(vertex, &spike));
+                assert!(
+                    distance_to_outline(vertex, &spike) >= 0.5 - 1e-4,
+                    "{join:?}"
+                );
             }
         }
-        // If line_end is not on a vertex, assume it's outside and directly check for any edge intersection
-        for i in 0..n {
-            let next_i = (i + 1) % n;
-            let v1 = &polygon.vertices[i];
-            let v2 = &polygon.vertices[next_i];
+    }
 
-            // Skip the edge that contains line_start
-            if Some(i) == line_start_edge_index {
-                continue;
+    #[test]
+    fn offset_cuts_off_loops_in_narrow_notches() {
+        // The notch is narrower than the two offsets, so its walls cross when grown
+        let mut notched = polygon(&[
+            (0.0, 0.0),
+            (4.0, 0.0),
+            (4.0, 4.0),
+            (2.2, 4.0),
+            (2.2, 1.0),
+            (1.8, 1.0),
+            (1.8, 4.0),
+            (0.0, 4.0),
+        ]);
+        notched.set_clockwise(true);
+        let inside_notch = Point {
+            x: 2.0,
+            y: 0.0,
+            z: 3.0,
+        };
+        assert!(!point_in_polygon(&inside_notch, &notched));
+
+        for join in [Join::default(), Join::Square, Join::Round] {
+            let offset = notched.offset(0.5, join);
+            assert!(offset.is_simple(), "{join:?}");
+            assert!(offset.is_clockwise(), "{join:?}");
+            assert!(point_in_polygon(&inside_notch, &offset), "{join:?}");
+            for vertex in &offset.vertices {
+                assert!(!point_in_polygon(vertex, &notched), "{join:?}");
+                assert!(
+                    distance_to_outline(vertex, &notched) >= 0.5 - 1e-4,
+                    "{join:?}"
+                );
             }
+        }
+    }
+
+    /// The orientation determinant in plain `f32`, as the predicates computed it before.
+    fn orient2d_f32(a: (f32, f32), b: (f32, f32), c: (f32, f32)) -> f32 {
+        (a.0 - c.0) * (b.1 - c.1) - (a.1 - c.1) * (b.0 - c.0)
+    }
 
-            if do_lines_intersect(line_start, line_end, v1, v2) {
-                return true;
+    /// The exact sign of the determinant, for coordinates that are multiples of 2^-24
+    /// and so become integers once scaled.
+    fn orient2d_exact(a: (f32, f32), b: (f32, f32), c: (f32, f32)) -> Ordering {
+        let scaled = |value: f32| {
+            let scaled = value as f64 * (1 << 24) as f64;
+            assert_eq!(scaled.fract(), 0.0);
+            scaled as i128
+        };
+        let (ax, ay, bx, by, cx, cy) = (
+            scaled(a.0),
+            scaled(a.1),
+            scaled(b.0),
+            scaled(b.1),
+            scaled(c.0),
+            scaled(c.1),
+        );
+        ((ax - cx) * (by - cy) - (ay - cy) * (bx - cx)).cmp(&0)
+    }
+
+    #[test]
+    fn orient2d_is_exact_near_collinear_points() {
+        // Points a few ulps off the line y = x, where rounding decides the f32 sign
+        let (b, c) = ((12.0, 12.0), (24.0, 24.0));
+        // The spacing of f32 values just above 0.5
+        let ulp = f32::EPSILON / 2.0;
+        let mut f32_wrong = 0;
+        for i in 0..32 {
+            for j in 0..32 {
+                let a = (0.5 + i as f32 * ulp, 0.5 + j as f32 * ulp);
+                let exact = orient2d_exact(a, b, c);
+                assert_eq!(orient2d(a, b, c).partial_cmp(&0.0), Some(exact), "{a:?}");
+                if orient2d_f32(a, b, c).partial_cmp(&0.0) != Some(exact) {
+                    f32_wrong += 1;
+                }
             }
         }
-        return false;
+        assert!(f32_wrong > 0);
     }
 
-    // Check if the line intersects with any of the polygon's edges
-    for i in 0..n {
-        let next_i = (i + 1) % n;
-        let v1 = &polygon.vertices[i];
-        let v2 = &polygon.vertices[next_i];
+    #[test]
+    fn orient2d_agrees_with_itself_under_permutation() {
+        let (a, b, c) = (
+            (0.5, 0.5),
+            (12.0, 12.0),
+            (24.0, f32::from_bits(24f32.to_bits() + 1)),
+        );
+        let sign = orient2d(a, b, c).signum();
+        assert_ne!(sign, 0.0);
+        assert_eq!(orient2d(b, c, a).signum(), sign);
+        assert_eq!(orient2d(c, a, b).signum(), sign);
+        assert_eq!(orient2d(b, a, c).signum(), -sign);
+        assert_eq!(orient2d(a, b, (36.0, 36.0)), 0.0);
+    }
 
-        // Skip this edge if line_start or line_end is exactly on one of the vertices of the polygon
-        if line_start == v1 || line_start == v2 || line_end == v1 || line_end == v2 {
-            continue;
+    #[test]
+    fn two_sum_keeps_the_rounding_error() {
+        let big = 2f64.powi(53);
+        assert_eq!(big + 1.0, big);
+        assert_eq!(two_sum(big, 1.0), (big, 1.0));
+        assert_eq!(two_sum(1.0, big), (big, 1.0));
+        assert_eq!(two_sum(0.1, 0.2), (0.1 + 0.2, -2f64.powi(-55)));
+    }
+
+    #[test]
+    fn grow_expansion_sums_exactly() {
+        let big = 2f64.powi(53);
+        assert_eq!((big + 1.0) - big, 0.0);
+
+        let mut expansion = Vec::new();
+        for value in [big, 1.0, -big] {
+            grow_expansion(&mut expansion, value);
         }
+        assert_eq!(expansion, vec![1.0]);
 
-        if do_lines_intersect(line_start, line_end, v1, v2) {
-            return true;
+        let mut expansion = Vec::new();
+        for value in [1.0, big, 1.0, 0.0] {
+            grow_expansion(&mut expansion, value);
+        }
+        assert_eq!(expansion, vec![big + 2.0]);
+
+        let mut expansion = Vec::new();
+        for value in [big, 1.0, 2f64.powi(-60)] {
+            grow_expansion(&mut expansion, value);
+        }
+        assert_eq!(expansion, vec![2f64.powi(-60), 1.0, big]);
+    }
+
+    /// The (x, z) corners of a polygon, to compare outlines.
+    fn corners(polygon: &Polygon) -> Vec<(f32, f32)> {
+        polygon
+            .vertices
+            .iter()
+            .map(|vertex| (vertex.x, vertex.z))
+            .collect()
+    }
+
+    /// Whether the outline has the expected corners in order, from any starting corner.
+    fn same_outline(polygon: &Polygon, expected: &[(f32, f32)]) -> bool {
+        let corners = corners(polygon);
+        corners.len() == expected.len()
+            && (0..expected.len()).any(|start| {
+                (0..expected.len()).all(|i| corners[(start + i) % corners.len()] == expected[i])
+            })
+    }
+
+    #[test]
+    fn repaired_rejects_outlines_without_area() {
+        assert_eq!(
+            Polygon::new().repaired().unwrap_err(),
+            PolygonError::TooFewVertices
+        );
+        let collinear = polygon(&[(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (3.0, 0.0)]);
+        assert_eq!(
+            collinear.repaired().unwrap_err(),
+            PolygonError::TooFewVertices
+        );
+        let repeated = polygon(&[(0.0, 0.0), (0.0, 0.0), (1.0, 1.0), (1.0, 1.0)]);
+        assert_eq!(
+            repeated.repaired().unwrap_err(),
+            PolygonError::TooFewVertices
+        );
+    }
+
+    #[test]
+    fn repaired_drops_duplicate_and_collinear_vertices() {
+        let square = polygon(&[
+            (0.0, 0.0),
+            (0.0, 0.0),
+            (1.0, 0.0),
+            (2.0, 0.0),
+            (2.0, 2.0),
+            (0.0, 2.0),
+            (0.0, 0.0),
+        ]);
+        let repaired = square.repaired().unwrap();
+        assert_eq!(repaired.vertices.len(), 4);
+        assert!(repaired.is_clockwise());
+        assert_eq!(repaired.signed_area().abs(), 4.0);
+    }
+
+    #[test]
+    fn repaired_keeps_the_larger_loop_of_a_bow_tie() {
+        // Edges 0 and 2 cross at (0.8, 0.8), leaving loops of area 6.4 and 0.4
+        let bow_tie = polygon(&[(0.0, 0.0), (4.0, 4.0), (4.0, 0.0), (0.0, 1.0)]);
+        assert_eq!(bow_tie.self_intersections(), vec![(0, 2)]);
+
+        let split = bow_tie.split_at_crossing(0, 2);
+        assert_eq!(split.vertices.len(), 3);
+        assert!((split.signed_area().abs() - 6.4).abs() < 1e-4);
+
+        let repaired = bow_tie.repaired().unwrap();
+        assert!(repaired.is_simple());
+        assert!(repaired.is_clockwise());
+        assert!((repaired.signed_area().abs() - 6.4).abs() < 1e-4);
+    }
+
+    #[test]
+    fn repaired_falls_back_to_the_hull_for_overlapping_edges() {
+        // The edge from (3, 0) to (1, 0) runs back over the first edge
+        let folded = polygon(&[
+            (0.0, 0.0),
+            (4.0, 0.0),
+            (4.0, -1.0),
+            (3.0, -1.0),
+            (3.0, 0.0),
+            (1.0, 0.0),
+            (1.0, 1.0),
+            (0.0, 1.0),
+        ]);
+        assert_eq!(
+            corners(&folded.split_at_crossing(0, 4)),
+            corners(&folded.convex_hull())
+        );
+
+        let repaired = folded.repaired().unwrap();
+        assert!(repaired.is_simple());
+        assert!(repaired.signed_area().abs() <= folded.convex_hull().signed_area().abs());
+    }
+
+    #[test]
+    fn simplification_handles_degenerate_outlines() {
+        let empty = Polygon::new();
+        assert!(empty.simplify_douglas_peucker(0.1).vertices.is_empty());
+        assert!(empty.simplify_visvalingam(0.1).vertices.is_empty());
+
+        // Nothing is left to simplify to, so the line comes back unchanged
+        let line = polygon(&[(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (3.0, 0.0)]);
+        assert_eq!(corners(&line.simplify_douglas_peucker(0.1)), corners(&line));
+        // Visvalingam stops at three vertices even when they have no area
+        assert_eq!(line.simplify_visvalingam(0.1).vertices.len(), 3);
+
+        let square = polygon(&[
+            (0.0, 0.0),
+            (1.0, 0.0),
+            (2.0, 0.0),
+            (2.0, 0.0),
+            (2.0, 2.0),
+            (0.0, 2.0),
+        ]);
+        let expected = vec![(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)];
+        assert_eq!(corners(&square.simplify_douglas_peucker(0.1)), expected);
+        assert_eq!(corners(&square.simplify_visvalingam(0.1)), expected);
+
+        // Repeats of the first vertex, the farthest one and the closing one
+        let repeated = polygon(&[
+            (0.0, 0.0),
+            (0.0, 0.0),
+            (2.0, 0.0),
+            (2.0, 2.0),
+            (2.0, 2.0),
+            (0.0, 2.0),
+            (0.0, 0.0),
+        ]);
+        let simplified = repeated.simplify_douglas_peucker(0.0);
+        assert!(same_outline(&simplified, &expected), "{simplified:?}");
+        let simplified = repeated.simplify_visvalingam(1e-6);
+        assert!(same_outline(&simplified, &expected), "{simplified:?}");
+    }
+
+    #[test]
+    fn centroid_needs_area() {
+        assert!(Polygon::new().centroid().is_none());
+        assert!(polygon(&[(0.0, 0.0), (1.0, 0.0), (2.0, 0.0)])
+            .centroid()
+            .is_none());
+        // The two loops of a symmetric bow-tie cancel out
+        assert!(polygon(&[(0.0, 0.0), (2.0, 2.0), (2.0, 0.0), (0.0, 2.0)])
+            .centroid()
+            .is_none());
+
+        let mut square = polygon(&[(0.0, 0.0), (2.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)]);
+        for clockwise in [true, false] {
+            square.set_clockwise(clockwise);
+            let centroid = square.centroid().unwrap();
+            assert_eq!((centroid.x, centroid.z), (1.0, 1.0));
         }
     }
-    false
 }
@@ -0,0 +1,105 @@
+use crate::obstacles::ObstaclePolygons;
+use crate::pathfinding::{find_path, NavMesh, PathQueryStats};
+use crate::player::{GizmoPath, TargetPosition};
+use crate::player_stats::PlayerStats;
+use crate::pursue::{Pursuable, Velocity};
+use crate::stuck_detection::PathProgress;
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+const WANDER_RANGE: f32 = 50.0;
+const WANDERER_COUNT: usize = 4;
+
+/// Units that roam between random destinations, giving follow and chase orders
+/// something to pursue.
+#[derive(Component)]
+pub struct Wanderer;
+
+/// Picks the wanderers' destinations. Seed it, for example from
+/// `WorldGenConfig::detail_rng`, to make their routes reproducible.
+#[derive(Resource)]
+pub struct WanderRng(pub StdRng);
+
+impl Default for WanderRng {
+    fn default() -> Self {
+        WanderRng(StdRng::seed_from_u64(0))
+    }
+}
+
+pub fn spawn_wanderers(
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+    obstacle_polygons: &ObstaclePolygons,
+    rng: &mut impl Rng,
+) {
+    for _ in 0..WANDERER_COUNT {
+        let Some(position) = random_open_point(rng, obstacle_polygons) else {
+            continue;
+        };
+
+        spawn_wanderer(commands, meshes, materials, position);
+    }
+}
+
+pub fn spawn_wanderer(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    position: Vec3,
+) {
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(Cuboid::new(0.8, 0.8, 0.8)),
+            material: materials.add(Color::srgb(0.8, 0.2, 0.2)),
+            transform: Transform::from_xyz(position.x, 0.4, position.z),
+            ..default()
+        },
+        Wanderer,
+        Pursuable,
+        Velocity::default(),
+        PlayerStats::new(3.0, 100.0, 1.0),
+        TargetPosition::default(),
+        GizmoPath::default(),
+        PathProgress::default(),
+    ));
+}
+
+pub fn wander(
+    mut wanderer_query: Query<(&Transform, &mut TargetPosition), With<Wanderer>>,
+    obstacle_polygons: Res<ObstaclePolygons>,
+    nav_mesh: Res<NavMesh>,
+    mut query_stats: Deferred<PathQueryStats>,
+    mut rng: ResMut<WanderRng>,
+) {
+    for (transform, mut target_position) in &mut wanderer_query {
+        if matches!(&target_position.0, Some(path) if !path.is_empty()) {
+            continue;
+        }
+
+        let Some(goal) = random_open_point(&mut rng.0, &obstacle_polygons) else {
+            continue;
+        };
+
+        target_position.0 = find_path(
+            &nav_mesh,
+            &obstacle_polygons,
+            transform.translation,
+            goal,
+            &mut query_stats,
+        );
+    }
+}
+
+fn random_open_point(rng: &mut impl Rng, obstacle_polygons: &ObstaclePolygons) -> Option<Vec3> {
+    (0..20)
+        .map(|_| {
+            Vec3::new(
+                rng.gen_range(-WANDER_RANGE..WANDER_RANGE),
+                0.0,
+                rng.gen_range(-WANDER_RANGE..WANDER_RANGE),
+            )
+        })
+        .find(|point| !obstacle_polygons.contains_point(*point))
+}